@@ -2,27 +2,212 @@
 
 use std::{
     convert::TryFrom,
+    fmt, io,
     io::{Read, Write},
     net::{SocketAddr, TcpStream, ToSocketAddrs},
     result::Result as StdResult,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use http::{request::Parts, HeaderName, Uri};
+use http::{
+    header::{AUTHORIZATION, COOKIE, HOST, PROXY_AUTHORIZATION},
+    request::Parts,
+    HeaderName, HeaderValue, Uri,
+};
 use log::*;
+use socket2::{Domain, Protocol, Socket, Type};
 
 use crate::{
     handshake::client::{generate_key, Request, Response},
     protocol::WebSocketConfig,
+    proxy::ProxyConfig,
     stream::MaybeTlsStream,
+    tls::{client_tls_with_server_name, Connector, TlsConnect, TlsOptions},
 };
 
 use crate::{
-    error::{Error, Result, UrlError},
-    handshake::{client::ClientHandshake, HandshakeError},
+    error::{ConnectError, Error, Result, UrlError},
+    handshake::{client::ClientHandshake, HandshakeConfig, HandshakeError},
     protocol::WebSocket,
     stream::{Mode, NoDelay},
 };
 
+/// Options for establishing a connection with [`connect_with_options`].
+#[derive(Clone)]
+pub struct ConnectOptions {
+    /// The WebSocket configuration, `None` means the default one.
+    pub config: Option<WebSocketConfig>,
+    /// How redirect responses to the handshake request are followed.
+    pub redirects: RedirectPolicy,
+    /// The proxy to connect through. The default is to connect directly.
+    pub proxy: ProxyConfig,
+    /// The timeout for establishing a TCP connection to a single address, `None` leaves it to
+    /// the operating system. It also limits each read and write while opening the tunnel through
+    /// a proxy. The default value is `None`.
+    pub connect_timeout: Option<Duration>,
+    /// The delay before racing a connection attempt to the next address while the previous
+    /// attempts are still pending ("Happy Eyeballs", RFC 8305). Addresses are tried
+    /// alternating between IPv6 and IPv4. `None` tries the addresses one after another.
+    /// The default value is 250 milliseconds.
+    pub happy_eyeballs_delay: Option<Duration>,
+    /// The resolver for host names of the server and the proxy. The default is
+    /// [`SystemResolver`].
+    pub resolver: Arc<dyn Resolver>,
+    /// The addresses to connect to instead of resolving the host of the request URI, e.g. taken
+    /// from a service registry. Through a proxy, the first address is requested from the proxy.
+    /// Redirects to another origin connect to the resolved addresses again. The default is to
+    /// resolve the host.
+    pub connect_addrs: Vec<SocketAddr>,
+    /// The server name sent with SNI and used to verify the server certificate instead of the
+    /// host of the request URI. Like `connect_addrs`, it is not used after redirects to another
+    /// origin. The default value is `None`.
+    pub server_name: Option<String>,
+    /// The `Host` header to send instead of the host and port of the request URI. Like
+    /// `connect_addrs`, it is not used after redirects to another origin. The default value is
+    /// `None`.
+    pub host_header: Option<String>,
+    /// The options for the TLS connector of `wss://` connections.
+    pub tls: TlsOptions,
+    /// The connector of a TLS library not supported by this crate, used for `wss://`
+    /// connections instead of the one configured by `tls`. The default value is `None`.
+    pub tls_connector: Option<Arc<dyn TlsConnect>>,
+}
+
+impl fmt::Debug for ConnectOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectOptions")
+            .field("config", &self.config)
+            .field("redirects", &self.redirects)
+            .field("proxy", &self.proxy)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("happy_eyeballs_delay", &self.happy_eyeballs_delay)
+            .field("connect_addrs", &self.connect_addrs)
+            .field("server_name", &self.server_name)
+            .field("host_header", &self.host_header)
+            .field("tls", &self.tls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions {
+            config: None,
+            redirects: RedirectPolicy::default(),
+            proxy: ProxyConfig::Direct,
+            connect_timeout: None,
+            happy_eyeballs_delay: Some(Duration::from_millis(250)),
+            resolver: Arc::new(SystemResolver),
+            connect_addrs: Vec::new(),
+            server_name: None,
+            host_header: None,
+            tls: TlsOptions::default(),
+            tls_connector: None,
+        }
+    }
+}
+
+/// Resolves host names to the addresses to connect to.
+///
+/// Implemented for closures taking the host name and port, so host names can be mapped to
+/// fixed addresses:
+///
+/// ```
+/// use std::{net::SocketAddr, sync::Arc};
+/// use tungstenite::client::ConnectOptions;
+///
+/// let local: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+/// let options = ConnectOptions {
+///     resolver: Arc::new(move |host: &str, _port: u16| match host {
+///         "example.com" => Ok(vec![local]),
+///         _ => Err(std::io::ErrorKind::NotFound.into()),
+///     }),
+///     ..ConnectOptions::default()
+/// };
+/// ```
+pub trait Resolver: Send + Sync {
+    /// Resolve the host name and port to socket addresses, in order of preference.
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync,
+{
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self(host, port)
+    }
+}
+
+/// The resolver of the operating system, as used by [`ToSocketAddrs`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// The policy for following redirect responses to the handshake request.
+///
+/// `Location` headers may be absolute or relative, relative ones are resolved against the URI of
+/// the request that was redirected.
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    /// The maximum number of redirects to follow. The default value is 3.
+    pub max_redirects: u8,
+    /// Whether a redirect from `wss://` to `ws://` may be followed. The default value is `false`.
+    pub allow_downgrade: bool,
+    /// Whether a redirect to another host may be followed. The default value is `true`.
+    pub allow_cross_host: bool,
+    /// Headers removed from the request before following a redirect to another origin, i.e. a
+    /// different scheme, host or port. By default these are `Authorization`, `Cookie` and
+    /// `Proxy-Authorization`.
+    pub sensitive_headers: Vec<HeaderName>,
+}
+
+impl RedirectPolicy {
+    /// A policy that does not follow any redirects.
+    pub fn none() -> Self {
+        RedirectPolicy { max_redirects: 0, ..RedirectPolicy::default() }
+    }
+
+    /// Check whether a redirect from `from` to `to` may be followed.
+    fn check(&self, from: &Uri, to: &Uri) -> Result<()> {
+        if !self.allow_downgrade
+            && from.scheme_str() == Some("wss")
+            && to.scheme_str() == Some("ws")
+        {
+            return Err(Error::Url(UrlError::InsecureRedirect));
+        }
+        if !self.allow_cross_host && !eq_host(from, to) {
+            return Err(Error::Url(UrlError::CrossHostRedirect(to.to_string())));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy {
+            max_redirects: 3,
+            allow_downgrade: false,
+            allow_cross_host: true,
+            sensitive_headers: vec![AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION],
+        }
+    }
+}
+
+/// The URIs requested while connecting, in order, the last one being the URI the connection
+/// was established with.
+///
+/// It is stored in the extensions of the [`Response`] returned by [`connect_with_options`] and
+/// the functions based on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectChain(pub Vec<Uri>);
+
 /// Connect to the given WebSocket in blocking mode.
 ///
 /// Uses a websocket configuration passed as an argument to the function. Calling it with `None` is
@@ -36,9 +221,10 @@ use crate::{
 /// similar to `std::net::TcpStream`. If you want a non-blocking or other
 /// custom stream, call `client` instead.
 ///
-/// This function uses `native_tls` or `rustls` to do TLS depending on the feature flags enabled. If
-/// you want to use other TLS libraries, use `client` instead. There is no need to enable any of
-/// the `*-tls` features if you don't call `connect` since it's the only function that uses them.
+/// This function uses `native_tls`, `openssl` or `rustls` to do TLS depending on the feature flags
+/// enabled. If you want to use other TLS libraries, use `client` instead. There is no need to
+/// enable any of the TLS features if you don't call `connect` since it's the only function that
+/// uses them.
 ///
 /// [readme]: https://github.com/snapview/tungstenite-rs/#features
 pub fn connect_with_config<Req: IntoClientRequest>(
@@ -46,15 +232,47 @@ pub fn connect_with_config<Req: IntoClientRequest>(
     config: Option<WebSocketConfig>,
     max_redirects: u8,
 ) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, Response)> {
+    let redirects = RedirectPolicy { max_redirects, ..RedirectPolicy::default() };
+    connect_with_options(request, ConnectOptions { config, redirects, ..ConnectOptions::default() })
+}
+
+/// Connect to the given WebSocket in blocking mode using the given options.
+///
+/// This function does the same as [`connect_with_config`] but allows to configure how the
+/// connection is established, e.g. to connect through a proxy.
+pub fn connect_with_options<Req: IntoClientRequest>(
+    request: Req,
+    options: ConnectOptions,
+) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, Response)> {
+    /// Connects with the overrides of the options for the dial target, the server name and the
+    /// `Host` header if `overrides` is set.
     fn try_client_handshake(
-        request: Request,
-        config: Option<WebSocketConfig>,
+        mut request: Request,
+        options: &ConnectOptions,
+        overrides: bool,
     ) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, Response)> {
+        #[cfg(unix)]
+        if request.uri().scheme_str() == Some(UNIX_SCHEME) {
+            let (path, request) = unix_socket_request(request)?;
+            debug!("Trying to contact {} at {}...", request.uri(), path);
+            let stream = std::os::unix::net::UnixStream::connect(path)?;
+            return client_with_config(request, MaybeTlsStream::Unix(stream), options.config)
+                .map_err(|e| match e {
+                    HandshakeError::Failure(f) => f,
+                    HandshakeError::Interrupted(_) => panic!("Bug: blocking handshake not blocked"),
+                });
+        }
+
+        if let (true, Some(host)) = (overrides, &options.host_header) {
+            request.headers_mut().insert(HOST, HeaderValue::from_str(host)?);
+        }
+        let connect_addrs = if overrides { &options.connect_addrs[..] } else { &[] };
+
         let uri = request.uri();
         let mode = uri_mode(uri)?;
 
-        #[cfg(not(any(feature = "native-tls", feature = "__rustls-tls")))]
-        if let Mode::Tls = mode {
+        #[cfg(not(any(feature = "native-tls", feature = "openssl", feature = "__rustls-tls")))]
+        if let (Mode::Tls, None) = (mode, &options.tls_connector) {
             return Err(Error::Url(UrlError::TlsFeatureNotEnabled));
         }
 
@@ -64,14 +282,54 @@ pub fn connect_with_config<Req: IntoClientRequest>(
             Mode::Plain => 80,
             Mode::Tls => 443,
         });
-        let addrs = (host, port).to_socket_addrs()?;
-        let mut stream = connect_to_some(addrs.as_slice(), request.uri())?;
+        let mut stream = match options.proxy.for_target(mode, host, port)? {
+            Some(proxy) => {
+                let (proxy_host, proxy_port) = proxy.host_and_port();
+                let addrs = options.resolver.resolve(proxy_host, proxy_port)?;
+                let stream = connect_to_some(&addrs, proxy.uri(), options)?;
+                let (resolver, timeout) = (options.resolver.as_ref(), options.connect_timeout);
+                match connect_addrs.first() {
+                    Some(addr) => proxy.tunnel(
+                        stream,
+                        &addr.ip().to_string(),
+                        addr.port(),
+                        resolver,
+                        timeout,
+                    )?,
+                    None => proxy.tunnel(stream, host, port, resolver, timeout)?,
+                }
+            }
+            None if !connect_addrs.is_empty() => {
+                connect_to_some(connect_addrs, request.uri(), options)?
+            }
+            None => {
+                let addrs = options.resolver.resolve(host, port)?;
+                connect_to_some(&addrs, request.uri(), options)?
+            }
+        };
         NoDelay::set_nodelay(&mut stream, true)?;
 
-        #[cfg(not(any(feature = "native-tls", feature = "__rustls-tls")))]
-        let client = client_with_config(request, MaybeTlsStream::Plain(stream), config);
-        #[cfg(any(feature = "native-tls", feature = "__rustls-tls"))]
-        let client = crate::tls::client_tls_with_config(request, stream, config, None);
+        let server_name = match (overrides, &options.server_name) {
+            (true, Some(server_name)) => server_name.clone(),
+            _ => host.to_owned(),
+        };
+        let connector = match (mode, &options.tls_connector) {
+            (Mode::Plain, _) => None,
+            (Mode::Tls, Some(connector)) => {
+                let connector = connector.clone();
+                Some(Connector::Custom(Box::new(
+                    move |stream: TcpStream, domain: &str, mode: Mode| {
+                        connector.connect(stream, domain, mode)
+                    },
+                )))
+            }
+            (Mode::Tls, None) if !options.tls.is_default() => {
+                Some(Connector::with_options(&options.tls)?)
+            }
+            (Mode::Tls, None) => None,
+        };
+        let client =
+            client_tls_with_server_name(request, stream, options.config, connector, &server_name);
 
         client.map_err(|e| match e {
             HandshakeError::Failure(f) => f,
@@ -86,24 +344,44 @@ pub fn connect_with_config<Req: IntoClientRequest>(
         builder.body(()).expect("Failed to create `Request`")
     }
 
-    let (parts, _) = request.into_client_request()?.into_parts();
+    let max_redirects = options.redirects.max_redirects;
+    let (mut parts, _) = request.into_client_request()?.into_parts();
     let mut uri = parts.uri.clone();
+    let mut chain = Vec::new();
 
     for attempt in 0..(max_redirects + 1) {
         let request = create_request(&parts, &uri);
+        chain.push(uri.clone());
 
-        match try_client_handshake(request, config) {
+        match try_client_handshake(request, &options, eq_origin(&uri, &parts.uri)) {
+            Ok((socket, mut response)) => {
+                response.extensions_mut().insert(RedirectChain(chain));
+                return Ok((socket, response));
+            }
             Err(Error::Http(res)) if res.status().is_redirection() && attempt < max_redirects => {
-                if let Some(location) = res.headers().get("Location") {
-                    uri = location.to_str()?.parse::<Uri>()?;
-                    debug!("Redirecting to {:?}", uri);
-                    continue;
-                } else {
-                    warn!("No `Location` found in redirect");
-                    return Err(Error::Http(res));
+                let location = match res.headers().get("Location") {
+                    Some(location) => location.to_str()?,
+                    None => {
+                        warn!("No `Location` found in redirect");
+                        return Err(Error::Http(res));
+                    }
+                };
+                let next = resolve_location(&uri, location)?;
+                options.redirects.check(&uri, &next)?;
+
+                if !eq_origin(&uri, &next) {
+                    for header in &options.redirects.sensitive_headers {
+                        parts.headers.remove(header);
+                    }
+                }
+                if parts.headers.contains_key(HOST) {
+                    parts.headers.insert(HOST, HeaderValue::from_str(host_with_port(&next)?)?);
                 }
+
+                debug!("Redirecting to {:?}", next);
+                uri = next;
             }
-            other => return other,
+            Err(e) => return Err(e),
         }
     }
 
@@ -113,29 +391,362 @@ pub fn connect_with_config<Req: IntoClientRequest>(
 /// Connect to the given WebSocket in blocking mode.
 ///
 /// The URL may be either ws:// or wss://.
-/// To support wss:// URLs, feature `native-tls` or `rustls-tls` must be turned on.
+/// To support wss:// URLs, feature `native-tls`, `openssl` or `rustls-tls` must be turned on.
+///
+/// On Unix, a `ws+unix:///path/to.sock:/resource` URL connects to `/resource` over the Unix domain
+/// socket at `/path/to.sock`. The socket path must not contain a `:`.
 ///
 /// This function "just works" for those who wants a simple blocking solution
 /// similar to `std::net::TcpStream`. If you want a non-blocking or other
 /// custom stream, call `client` instead.
 ///
-/// This function uses `native_tls` or `rustls` to do TLS depending on the feature flags enabled. If
-/// you want to use other TLS libraries, use `client` instead. There is no need to enable any of
-/// the `*-tls` features if you don't call `connect` since it's the only function that uses them.
+/// This function uses `native_tls`, `openssl` or `rustls` to do TLS depending on the feature flags
+/// enabled. If you want to use other TLS libraries, use `client` instead. There is no need to
+/// enable any of the TLS features if you don't call `connect` since it's the only function that
+/// uses them.
 pub fn connect<Req: IntoClientRequest>(
     request: Req,
 ) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, Response)> {
     connect_with_config(request, None, 3)
 }
 
-fn connect_to_some(addrs: &[SocketAddr], uri: &Uri) -> Result<TcpStream> {
-    for addr in addrs {
-        debug!("Trying to contact {} at {}...", uri, addr);
-        if let Ok(stream) = TcpStream::connect(addr) {
-            return Ok(stream);
+fn connect_to_some(addrs: &[SocketAddr], uri: &Uri, options: &ConnectOptions) -> Result<TcpStream> {
+    if addrs.is_empty() {
+        return Err(Error::Url(UrlError::UnableToConnect(uri.to_string())));
+    }
+
+    let mut attempts = Vec::new();
+    let addrs = interleave_families(addrs);
+    match options.happy_eyeballs_delay {
+        Some(delay) if addrs.len() > 1 => {
+            if let Some(stream) =
+                connect_racing(addrs, uri, delay, options.connect_timeout, &mut attempts)
+            {
+                return Ok(stream);
+            }
+        }
+        _ => {
+            for addr in addrs {
+                debug!("Trying to contact {} at {}...", uri, addr);
+                let result = match options.connect_timeout {
+                    Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                    None => TcpStream::connect(addr),
+                };
+                match result {
+                    Ok(stream) => return Ok(stream),
+                    Err(err) => {
+                        debug!("Failed to connect to {}: {}", addr, err);
+                        attempts.push((addr, err));
+                    }
+                }
+            }
+        }
+    }
+    Err(Error::Connect(ConnectError { uri: uri.to_string(), attempts }))
+}
+
+/// A connection attempt in progress.
+struct Attempt {
+    addr: SocketAddr,
+    socket: Socket,
+    deadline: Option<Instant>,
+}
+
+/// Race connection attempts to the addresses, starting the next one after `delay` or as soon
+/// as an attempt failed (RFC 8305). The attempts are non-blocking connects the calling thread
+/// waits on with `poll`, the losing ones are closed once a connection is established.
+fn connect_racing(
+    addrs: Vec<SocketAddr>,
+    uri: &Uri,
+    delay: Duration,
+    timeout: Option<Duration>,
+    failed: &mut Vec<(SocketAddr, io::Error)>,
+) -> Option<TcpStream> {
+    let mut pending = addrs.into_iter();
+    let mut racing: Vec<Attempt> = Vec::new();
+    let mut next_start = Some(Instant::now());
+    loop {
+        let now = Instant::now();
+        if next_start.map_or(false, |start| now >= start) {
+            if let Some(addr) = pending.next() {
+                debug!("Trying to contact {} at {}...", uri, addr);
+                next_start = now.checked_add(delay);
+                match start_connect(addr) {
+                    Ok(socket) => {
+                        let deadline = timeout.and_then(|timeout| now.checked_add(timeout));
+                        racing.push(Attempt { addr, socket, deadline });
+                    }
+                    Err(err) => {
+                        debug!("Failed to connect to {}: {}", addr, err);
+                        failed.push((addr, err));
+                        next_start = Some(now);
+                    }
+                }
+                continue;
+            }
+        }
+        if racing.is_empty() {
+            if pending.len() == 0 {
+                return None;
+            }
+            next_start = Some(now);
+            continue;
+        }
+
+        let mut i = 0;
+        while i < racing.len() {
+            let attempt = &racing[i];
+            let result = match poll_connect(&attempt.socket) {
+                Ok(false) if attempt.deadline.map_or(false, |deadline| now >= deadline) => {
+                    Err(io::ErrorKind::TimedOut.into())
+                }
+                Ok(true) => attempt.socket.set_nonblocking(false).map(|()| true),
+                result => result,
+            };
+            match result {
+                Ok(true) => {
+                    let attempt = racing.swap_remove(i);
+                    return Some(attempt.socket.into());
+                }
+                Ok(false) => i += 1,
+                Err(err) => {
+                    let attempt = racing.remove(i);
+                    debug!("Failed to connect to {}: {}", attempt.addr, err);
+                    failed.push((attempt.addr, err));
+                    next_start = Some(now);
+                }
+            }
+        }
+        if racing.is_empty() {
+            continue;
+        }
+
+        // Sleep until an attempt completes, the next one is due or an attempt times out.
+        let next_start = next_start.filter(|_| pending.len() > 0);
+        let wake = racing.iter().filter_map(|attempt| attempt.deadline).chain(next_start).min();
+        let wait = wake.map(|wake| wake.saturating_duration_since(Instant::now()));
+        if let Err(err) = wait_connected(&racing, wait) {
+            for attempt in racing {
+                debug!("Failed to wait for the connection to {}: {}", attempt.addr, err);
+                failed.push((attempt.addr, io::Error::new(err.kind(), err.to_string())));
+            }
+            return None;
+        }
+    }
+}
+
+/// Start connecting to the address without waiting for the connection to be established.
+fn start_connect(addr: SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_nonblocking(true)?;
+    match socket.connect(&addr.into()) {
+        // The connection attempt continues.
+        #[cfg(unix)]
+        Err(err) if err.raw_os_error() == Some(libc::EINPROGRESS) => Ok(socket),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(socket),
+        Err(err) => Err(err),
+        Ok(()) => Ok(socket),
+    }
+}
+
+/// Check whether a connection attempt succeeded, `false` while it is still in progress.
+fn poll_connect(socket: &Socket) -> io::Result<bool> {
+    if let Some(err) = socket.take_error()? {
+        return Err(err);
+    }
+    match socket.peer_addr() {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotConnected => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The timeout of `poll` in milliseconds, rounded up so it doesn't return early. `-1` waits
+/// without a timeout.
+fn poll_timeout(timeout: Option<Duration>) -> i32 {
+    timeout.map_or(-1, |timeout| {
+        let millis = (timeout.as_nanos() + 999_999) / 1_000_000;
+        i32::try_from(millis).unwrap_or(i32::MAX)
+    })
+}
+
+/// Wait until one of the connection attempts completes or fails, or the timeout elapses.
+#[cfg(unix)]
+fn wait_connected(attempts: &[Attempt], timeout: Option<Duration>) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut fds: Vec<libc::pollfd> = attempts
+        .iter()
+        .map(|attempt| libc::pollfd {
+            fd: attempt.socket.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        })
+        .collect();
+    // SAFETY: `fds` is a valid array of `fds.len()` entries for the duration of the call.
+    let result =
+        unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, poll_timeout(timeout)) };
+    match result {
+        -1 => match io::Error::last_os_error() {
+            err if err.kind() == io::ErrorKind::Interrupted => Ok(()),
+            err => Err(err),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Wait until one of the connection attempts completes or fails, or the timeout elapses.
+#[cfg(windows)]
+fn wait_connected(attempts: &[Attempt], timeout: Option<Duration>) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use windows_sys::Win32::Networking::WinSock::{
+        WSAGetLastError, WSAPoll, POLLOUT, SOCKET, SOCKET_ERROR, WSAPOLLFD,
+    };
+
+    let mut fds: Vec<WSAPOLLFD> = attempts
+        .iter()
+        .map(|attempt| WSAPOLLFD {
+            fd: attempt.socket.as_raw_socket() as SOCKET,
+            events: POLLOUT,
+            revents: 0,
+        })
+        .collect();
+    // SAFETY: `fds` is a valid array of `fds.len()` entries for the duration of the call.
+    let result = unsafe { WSAPoll(fds.as_mut_ptr(), fds.len() as u32, poll_timeout(timeout)) };
+    if result == SOCKET_ERROR {
+        // SAFETY: Only reads the error of the calling thread.
+        return Err(io::Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+    }
+    Ok(())
+}
+
+/// Order the addresses alternating between the address families, starting with the family of
+/// the first address (RFC 8305, section 4).
+fn interleave_families(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().map_or(false, SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        addrs.iter().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+
+    let mut ordered = Vec::with_capacity(addrs.len());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return ordered,
+            (first, second) => ordered.extend(first.into_iter().chain(second)),
+        }
+    }
+}
+
+/// The URL scheme for WebSockets over Unix domain sockets.
+const UNIX_SCHEME: &str = "ws+unix";
+
+/// Split a `ws+unix://localhost/path.sock:/resource` request into the socket path and the
+/// request for the resource.
+#[cfg(unix)]
+fn unix_socket_request(request: Request) -> Result<(String, Request)> {
+    let (mut parts, body) = request.into_parts();
+    let (path, resource) = match parts.uri.path().find(':') {
+        Some(idx) => parts.uri.path().split_at(idx),
+        None => (parts.uri.path(), ":/"),
+    };
+    let resource = match &resource[1..] {
+        "" => "/",
+        resource => resource,
+    };
+    let query = parts.uri.query().map(|q| format!("?{q}")).unwrap_or_default();
+    let uri = format!("ws://localhost{resource}{query}").parse()?;
+    let path = path.to_owned();
+    parts.uri = uri;
+    Ok((path, Request::from_parts(parts, body)))
+}
+
+/// Resolve a `Location` header value against the URI of the redirected request (RFC 3986,
+/// section 5.2).
+fn resolve_location(base: &Uri, location: &str) -> Result<Uri> {
+    // Fragments are not sent to the server.
+    let location = location.split('#').next().unwrap_or_default();
+
+    let has_scheme = location
+        .find(':')
+        .map(|colon| {
+            let scheme = &location[..colon];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+        .unwrap_or(false);
+    if has_scheme {
+        return Ok(location.parse()?);
+    }
+
+    let scheme = base.scheme_str().ok_or(Error::Url(UrlError::UnsupportedUrlScheme))?;
+    if location.starts_with("//") {
+        return Ok(format!("{scheme}:{location}").parse()?);
+    }
+
+    let authority = base.authority().ok_or(Error::Url(UrlError::NoHostName))?;
+    let (path, query) = match location.find('?') {
+        Some(idx) => location.split_at(idx),
+        None => (location, ""),
+    };
+    let base_query = base.query().map(|q| format!("?{q}")).unwrap_or_default();
+    let (path, query) = if path.is_empty() {
+        (base.path().to_owned(), if query.is_empty() { base_query.as_str() } else { query })
+    } else if path.starts_with('/') {
+        (remove_dot_segments(path), query)
+    } else {
+        let base_path = base.path();
+        let directory = &base_path[..base_path.rfind('/').map_or(0, |idx| idx + 1)];
+        (remove_dot_segments(&format!("/{}{path}", directory.trim_start_matches('/'))), query)
+    };
+    Ok(format!("{scheme}://{authority}{path}{query}").parse()?)
+}
+
+/// Remove `.` and `..` segments from an absolute path.
+fn remove_dot_segments(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut output: Vec<&str> = Vec::with_capacity(segments.len());
+    for (idx, segment) in segments.iter().enumerate() {
+        match *segment {
+            "." => {}
+            ".." => {
+                // Never remove the empty segment before the leading slash.
+                if output.len() > 1 {
+                    output.pop();
+                }
+            }
+            segment => output.push(segment),
         }
+        // A trailing dot segment refers to a directory.
+        if idx == segments.len() - 1 && (*segment == "." || *segment == "..") {
+            output.push("");
+        }
+    }
+    output.join("/")
+}
+
+/// The host and port of the URI without user information, as sent in the `Host` header.
+fn host_with_port(uri: &Uri) -> Result<&str> {
+    let authority = uri.authority().ok_or(Error::Url(UrlError::NoHostName))?.as_str();
+    Ok(authority.find('@').map(|idx| authority.split_at(idx + 1).1).unwrap_or(authority))
+}
+
+/// Whether both URIs point to the same host.
+fn eq_host(a: &Uri, b: &Uri) -> bool {
+    match (a.host(), b.host()) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
     }
-    Err(Error::Url(UrlError::UnableToConnect(uri.to_string())))
+}
+
+/// Whether both URIs have the same scheme, host and port.
+fn eq_origin(a: &Uri, b: &Uri) -> bool {
+    let port = |uri: &Uri| {
+        uri.port_u16().or_else(|| match uri_mode(uri).ok()? {
+            Mode::Plain => Some(80),
+            Mode::Tls => Some(443),
+        })
+    };
+    a.scheme() == b.scheme() && eq_host(a, b) && port(a) == port(b)
 }
 
 /// Get the mode of the given URL.
@@ -165,7 +776,29 @@ where
     Stream: Read + Write,
     Req: IntoClientRequest,
 {
-    ClientHandshake::start(stream, request.into_client_request()?, config)?.handshake()
+    client_with_handshake_config(request, stream, config, None)
+}
+
+/// Do the client handshake over the given stream given a web socket configuration and limits
+/// for reading the HTTP response. Passing `None` as `handshake_config` is equal to calling
+/// `client_with_config()` function.
+pub fn client_with_handshake_config<Stream, Req>(
+    request: Req,
+    stream: Stream,
+    config: Option<WebSocketConfig>,
+    handshake_config: Option<HandshakeConfig>,
+) -> StdResult<(WebSocket<Stream>, Response), HandshakeError<ClientHandshake<Stream>>>
+where
+    Stream: Read + Write,
+    Req: IntoClientRequest,
+{
+    ClientHandshake::start_with_handshake_config(
+        stream,
+        request.into_client_request()?,
+        config,
+        handshake_config,
+    )?
+    .handshake()
 }
 
 /// Do the client handshake over the given stream.
@@ -199,6 +832,12 @@ pub trait IntoClientRequest {
 
 impl<'a> IntoClientRequest for &'a str {
     fn into_client_request(self) -> Result<Request> {
+        // `Uri` requires a host, `ws+unix:///path.sock:/resource` has none.
+        if let Some(path) = self.strip_prefix("ws+unix:///") {
+            return format!("{UNIX_SCHEME}://localhost/{path}")
+                .parse::<Uri>()?
+                .into_client_request();
+        }
         self.parse::<Uri>()?.into_client_request()
     }
 }
@@ -223,11 +862,7 @@ impl<'a> IntoClientRequest for &'a Uri {
 
 impl IntoClientRequest for Uri {
     fn into_client_request(self) -> Result<Request> {
-        let authority = self.authority().ok_or(Error::Url(UrlError::NoHostName))?.as_str();
-        let host = authority
-            .find('@')
-            .map(|idx| authority.split_at(idx + 1).1)
-            .unwrap_or_else(|| authority);
+        let host = host_with_port(&self)?;
 
         if host.is_empty() {
             return Err(Error::Url(UrlError::EmptyHostName));
@@ -342,3 +977,129 @@ impl IntoClientRequest for ClientRequestBuilder {
         Ok(request)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_relative_location() {
+        let base: Uri = "ws://example.com:8080/a/b/socket?token=1".parse().unwrap();
+        let resolve = |location| resolve_location(&base, location).unwrap().to_string();
+
+        assert_eq!(resolve("wss://other.com/ws"), "wss://other.com/ws");
+        assert_eq!(resolve("//other.com/ws"), "ws://other.com/ws");
+        assert_eq!(resolve("/ws/v2"), "ws://example.com:8080/ws/v2");
+        assert_eq!(resolve("/ws/./v2/../v3?x=y#frag"), "ws://example.com:8080/ws/v3?x=y");
+        assert_eq!(resolve("v2"), "ws://example.com:8080/a/b/v2");
+        assert_eq!(resolve("../../../v2"), "ws://example.com:8080/v2");
+        assert_eq!(resolve(".."), "ws://example.com:8080/a/");
+        assert_eq!(resolve("?token=2"), "ws://example.com:8080/a/b/socket?token=2");
+        assert_eq!(resolve(""), "ws://example.com:8080/a/b/socket?token=1");
+    }
+
+    #[test]
+    fn redirect_policy() {
+        let secure: Uri = "wss://example.com/socket".parse().unwrap();
+        let plain: Uri = "ws://example.com/socket".parse().unwrap();
+        let other: Uri = "wss://other.com/socket".parse().unwrap();
+
+        let policy = RedirectPolicy::default();
+        assert!(matches!(
+            policy.check(&secure, &plain),
+            Err(Error::Url(UrlError::InsecureRedirect))
+        ));
+        assert!(policy.check(&plain, &secure).is_ok());
+        assert!(policy.check(&secure, &other).is_ok());
+
+        let policy = RedirectPolicy { allow_downgrade: true, allow_cross_host: false, ..policy };
+        assert!(policy.check(&secure, &plain).is_ok());
+        assert!(matches!(
+            policy.check(&secure, &other),
+            Err(Error::Url(UrlError::CrossHostRedirect(_)))
+        ));
+
+        assert!(eq_origin(&secure, &"wss://EXAMPLE.com:443/other".parse().unwrap()));
+        assert!(!eq_origin(&secure, &plain));
+        assert!(!eq_origin(&secure, &"wss://example.com:8443/socket".parse().unwrap()));
+    }
+
+    #[test]
+    fn address_interleaving() {
+        let addrs: Vec<SocketAddr> =
+            ["[::1]:1", "[::2]:1", "[::3]:1", "127.0.0.1:1", "127.0.0.2:1"]
+                .iter()
+                .map(|addr| addr.parse().unwrap())
+                .collect();
+        let ordered = interleave_families(&addrs);
+        assert_eq!(ordered, [addrs[0], addrs[3], addrs[1], addrs[4], addrs[2]]);
+
+        let ordered = interleave_families(&[addrs[3], addrs[0], addrs[4]]);
+        assert_eq!(ordered, [addrs[3], addrs[0], addrs[4]]);
+    }
+
+    /// An address nothing listens on.
+    fn closed_addr() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[test]
+    fn connect_errors_are_aggregated() {
+        let uri: Uri = "ws://example.com/socket".parse().unwrap();
+        let addrs = [closed_addr(), closed_addr()];
+        for delay in [None, Some(Duration::from_millis(10))] {
+            let options = ConnectOptions { happy_eyeballs_delay: delay, ..Default::default() };
+            match connect_to_some(&addrs, &uri, &options) {
+                Err(Error::Connect(err)) => {
+                    assert_eq!(err.uri(), "ws://example.com/socket");
+                    let mut failed: Vec<_> = err.attempts().iter().map(|(addr, _)| *addr).collect();
+                    failed.sort();
+                    let mut expected = addrs.to_vec();
+                    expected.sort();
+                    assert_eq!(failed, expected);
+                }
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn connect_races_addresses() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri: Uri = "ws://example.com/socket".parse().unwrap();
+        let addrs = [closed_addr(), listener.local_addr().unwrap()];
+        let options = ConnectOptions {
+            connect_timeout: Some(Duration::from_secs(5)),
+            ..ConnectOptions::default()
+        };
+        let stream = connect_to_some(&addrs, &uri, &options).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addrs[1]);
+        // The attempt was non-blocking, the stream must not be.
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: Only reads the flags of an open file descriptor.
+            let flags = unsafe { libc::fcntl(stream.as_raw_fd(), libc::F_GETFL) };
+            assert_eq!(flags & libc::O_NONBLOCK, 0);
+        }
+    }
+
+    #[test]
+    fn racing_attempts_time_out() {
+        let uri: Uri = "ws://example.com/socket".parse().unwrap();
+        // Documentation addresses (RFC 5737), either unreachable or never answering.
+        let addrs = ["192.0.2.1:80".parse().unwrap(), "192.0.2.2:80".parse().unwrap()];
+        let options = ConnectOptions {
+            connect_timeout: Some(Duration::from_millis(50)),
+            happy_eyeballs_delay: Some(Duration::from_millis(10)),
+            ..ConnectOptions::default()
+        };
+        let start = Instant::now();
+        match connect_to_some(&addrs, &uri, &options) {
+            Err(Error::Connect(err)) => assert_eq!(err.attempts().len(), 2),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
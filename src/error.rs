@@ -1,6 +1,6 @@
 //! Error handling.
 
-use std::{io, result, str, string};
+use std::{fmt, io, net::SocketAddr, result, str, string};
 
 use crate::protocol::{frame::coding::Data, Message};
 #[cfg(feature = "handshake")]
@@ -12,6 +12,7 @@ pub type Result<T, E = Error> = result::Result<T, E>;
 
 /// Possible WebSocket errors.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// WebSocket connection closed normally. This informs you of the close.
     /// It's not an error as such and nothing wrong happened.
@@ -37,6 +38,9 @@ pub enum Error {
     AlreadyClosed,
     /// Input-output error. Apart from WouldBlock, these are generally errors with the
     /// underlying connection and you should probably consider them fatal.
+    ///
+    /// TLS errors occurring after the TLS handshake, or in a TLS handshake resumed on a
+    /// non-blocking stream, are reported as I/O errors wrapping the error of the TLS library.
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
     /// TLS error.
@@ -65,6 +69,12 @@ pub enum Error {
     /// Invalid URL.
     #[error("URL error: {0}")]
     Url(#[from] UrlError),
+    /// Failed to connect to any of the addresses of the host.
+    #[error("{0}")]
+    Connect(#[from] ConnectError),
+    /// Proxy error.
+    #[error("Proxy error: {0}")]
+    Proxy(#[from] ProxyError),
     /// HTTP error.
     #[error("HTTP error: {}", .0.status())]
     #[cfg(feature = "handshake")]
@@ -134,11 +144,19 @@ impl From<httparse::Error> for Error {
 
 /// Indicates the specific type/cause of a capacity error.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
 pub enum CapacityError {
     /// Too many headers provided (see [`httparse::Error::TooManyHeaders`]).
     #[error("Too many headers")]
     TooManyHeaders,
-    /// Received header is too long.
+    /// Received header line is too long.
+    #[error("Header line too long: {size} > {max_size}")]
+    HeaderTooLong {
+        /// The size of the header line.
+        size: usize,
+        /// The maximum allowed header line size.
+        max_size: usize,
+    },
     /// Message is bigger than the maximum allowed size.
     #[error("Message too long: {size} > {max_size}")]
     MessageTooLong {
@@ -169,8 +187,10 @@ pub enum SubProtocolError {
 /// Indicates the specific type/cause of a protocol error.
 #[allow(missing_copy_implementations)]
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum ProtocolError {
-    /// Use of the wrong HTTP method (the WebSocket protocol requires the GET method be used).
+    /// Use of the wrong HTTP method (the WebSocket protocol requires the GET method be used,
+    /// or CONNECT when bootstrapping over HTTP/2).
     #[error("Unsupported HTTP method used - only GET is allowed")]
     WrongHttpMethod,
     /// Wrong HTTP version used (the WebSocket protocol requires version 1.1 or higher).
@@ -185,6 +205,9 @@ pub enum ProtocolError {
     /// Missing `Sec-WebSocket-Version: 13` HTTP header.
     #[error("No \"Sec-WebSocket-Version: 13\" header")]
     MissingSecWebSocketVersionHeader,
+    /// Missing `:protocol: websocket` pseudo-header in an HTTP/2 extended CONNECT request.
+    #[error("No \":protocol: websocket\" pseudo-header")]
+    MissingConnectProtocol,
     /// Missing `Sec-WebSocket-Key` HTTP header.
     #[error("No \"Sec-WebSocket-Key\" header")]
     MissingSecWebSocketKey,
@@ -195,6 +218,9 @@ pub enum ProtocolError {
     #[error("SubProtocol error: {0}")]
     SecWebSocketSubProtocolError(SubProtocolError),
     /// Garbage data encountered after client request.
+    #[deprecated(
+        note = "No longer produced, data after the request is passed on to the WebSocket"
+    )]
     #[error("Junk after client request")]
     JunkAfterRequest,
     /// Custom responses must be unsuccessful.
@@ -207,6 +233,9 @@ pub enum ProtocolError {
     /// No more data while still performing handshake.
     #[error("Handshake not finished")]
     HandshakeIncomplete,
+    /// The handshake did not complete before its deadline.
+    #[error("Handshake timed out")]
+    HandshakeTimedOut,
     /// Wrapper around a [`httparse::Error`] value.
     #[error("httparse error: {0}")]
     #[cfg(feature = "handshake")]
@@ -214,6 +243,9 @@ pub enum ProtocolError {
     /// Not allowed to send after having sent a closing frame.
     #[error("Sending after closing is not allowed")]
     SendAfterClosing,
+    /// Prepared messages are not masked and can't be sent by clients.
+    #[error("Prepared messages can only be sent by servers")]
+    PreparedMessageFromClient,
     /// Remote sent data after sending a closing frame.
     #[error("Remote sent after having closed")]
     ReceivedAfterClosing,
@@ -257,6 +289,7 @@ pub enum ProtocolError {
 
 /// Indicates the specific type/cause of URL error.
 #[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum UrlError {
     /// TLS is used despite not being compiled with the TLS feature enabled.
     #[error("TLS support not compiled in")]
@@ -276,6 +309,69 @@ pub enum UrlError {
     /// The URL does not include a path/query.
     #[error("No path/query in URL")]
     NoPathOrQuery,
+    /// A redirect from `wss://` to `ws://` was refused by the redirect policy.
+    #[error("Redirect from wss:// to ws:// not allowed")]
+    InsecureRedirect,
+    /// A redirect to another host was refused by the redirect policy.
+    #[error("Redirect to another host not allowed: {0}")]
+    CrossHostRedirect(String),
+}
+
+/// Failure to connect to any of the addresses a host name resolved to.
+#[derive(Debug)]
+pub struct ConnectError {
+    pub(crate) uri: String,
+    pub(crate) attempts: Vec<(SocketAddr, io::Error)>,
+}
+
+impl ConnectError {
+    /// The URI connected to.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// The addresses tried in order of failure, with the error returned for each of them.
+    pub fn attempts(&self) -> &[(SocketAddr, io::Error)] {
+        &self.attempts
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unable to connect to {}", self.uri)?;
+        for (idx, (addr, err)) in self.attempts.iter().enumerate() {
+            let separator = if idx == 0 { ": " } else { ", " };
+            write!(f, "{separator}{addr} ({err})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/// Indicates the specific type/cause of a proxy error.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ProxyError {
+    /// Unsupported proxy URL scheme used.
+    #[error("Proxy URL scheme not supported")]
+    UnsupportedScheme,
+    /// The proxy refused to open a tunnel, the status code of its response is included.
+    #[error("Proxy refused to connect: {0}")]
+    ConnectFailed(u16),
+    /// The proxy sent an invalid response.
+    #[error("Invalid response from proxy")]
+    InvalidResponse,
+    /// The SOCKS5 proxy rejected the offered authentication methods or credentials.
+    #[error("SOCKS5 authentication failed")]
+    SocksAuthenticationFailed,
+    /// The SOCKS5 proxy failed to connect, the reply code is included.
+    #[error("SOCKS5 proxy failed to connect: reply code {0}")]
+    SocksConnectFailed(u8),
+    /// The user name or password in the proxy URL is not valid percent-encoded UTF-8, or it is
+    /// too long to be sent to the SOCKS5 proxy.
+    #[error("Invalid proxy credentials")]
+    InvalidCredentials,
 }
 
 /// TLS errors.
@@ -290,12 +386,72 @@ pub enum TlsError {
     #[cfg(feature = "native-tls")]
     #[error("native-tls error: {0}")]
     Native(#[from] native_tls_crate::Error),
+    /// OpenSSL error.
+    #[cfg(feature = "openssl")]
+    #[error("openssl error: {0}")]
+    OpenSsl(#[from] openssl::ssl::Error),
     /// Rustls error.
     #[cfg(feature = "__rustls-tls")]
     #[error("rustls error: {0}")]
-    Rustls(#[from] rustls::Error),
+    Rustls(rustls::Error),
     /// DNS name resolution error.
     #[cfg(feature = "__rustls-tls")]
     #[error("Invalid DNS name")]
     InvalidDnsName,
+    /// None of the certificates presented by the server matches the pinned public keys.
+    #[cfg(feature = "__rustls-tls")]
+    #[error("Server certificate does not match the pinned public keys")]
+    PinMismatch,
+    /// Root verification is disabled without public key pins or a verification callback, which
+    /// would accept any server certificate.
+    #[error("Server certificates would not be verified")]
+    NoVerification,
+    /// The certificate verification callback rejected the server certificate.
+    #[cfg(feature = "__rustls-tls")]
+    #[error("Server certificate rejected: {0}")]
+    CertificateRejected(String),
+    /// The client certificate or its private key could not be parsed.
+    #[error("Invalid client identity: {0}")]
+    InvalidIdentity(String),
+    /// An ALPN protocol name is empty or longer than 255 bytes.
+    #[error("Invalid ALPN protocol name: {0:?}")]
+    InvalidAlpnProtocol(String),
+    /// The TLS library does not support an option of the connector.
+    #[error("Not supported by the TLS library: {0}")]
+    Unsupported(&'static str),
+}
+
+#[cfg(feature = "openssl")]
+impl From<openssl::error::ErrorStack> for TlsError {
+    fn from(err: openssl::error::ErrorStack) -> Self {
+        TlsError::OpenSsl(err.into())
+    }
+}
+
+#[cfg(feature = "__rustls-tls")]
+impl From<rustls::Error> for TlsError {
+    fn from(err: rustls::Error) -> Self {
+        if let rustls::Error::InvalidCertificate(rustls::CertificateError::Other(other)) = &err {
+            match other.0.downcast_ref::<CertificateRejection>() {
+                Some(CertificateRejection::PinMismatch) => return TlsError::PinMismatch,
+                Some(CertificateRejection::Rejected(reason)) => {
+                    return TlsError::CertificateRejected(reason.clone())
+                }
+                None => {}
+            }
+        }
+        TlsError::Rustls(err)
+    }
+}
+
+/// A server certificate rejected by the checks of [`TlsOptions`](crate::TlsOptions), carried
+/// through `rustls` and turned into the corresponding [`TlsError`].
+#[cfg(feature = "__rustls-tls")]
+#[cfg_attr(not(feature = "handshake"), allow(dead_code))]
+#[derive(Error, Debug)]
+pub(crate) enum CertificateRejection {
+    #[error("Server certificate does not match the pinned public keys")]
+    PinMismatch,
+    #[error("Server certificate rejected: {0}")]
+    Rejected(String),
 }
@@ -3,6 +3,7 @@
 use std::{
     io::{Read, Write},
     marker::PhantomData,
+    time::Instant,
 };
 
 use http::{
@@ -13,9 +14,9 @@ use log::*;
 
 use super::{
     derive_accept_key,
-    headers::{FromHttparse, MAX_HEADERS},
+    headers::{check_header_lines, with_header_buffer, FromHttparse},
     machine::{HandshakeMachine, StageResult, TryParse},
-    HandshakeRole, MidHandshake, ProcessingResult,
+    HandshakeConfig, HandshakeRole, MidHandshake, ProcessingResult,
 };
 use crate::{
     error::{Error, ProtocolError, Result, SubProtocolError, UrlError},
@@ -33,6 +34,8 @@ pub type Response = HttpResponse<Option<Vec<u8>>>;
 pub struct ClientHandshake<S> {
     verify_data: VerifyData,
     config: Option<WebSocketConfig>,
+    handshake_config: HandshakeConfig,
+    deadline: Option<Instant>,
     _marker: PhantomData<S>,
 }
 
@@ -42,6 +45,17 @@ impl<S: Read + Write> ClientHandshake<S> {
         stream: S,
         request: Request,
         config: Option<WebSocketConfig>,
+    ) -> Result<MidHandshake<Self>> {
+        Self::start_with_handshake_config(stream, request, config, None)
+    }
+
+    /// Initiate a client handshake with limits for reading the HTTP response. Passing `None` as
+    /// `handshake_config` is equal to calling [`start()`](Self::start).
+    pub fn start_with_handshake_config(
+        stream: S,
+        request: Request,
+        config: Option<WebSocketConfig>,
+        handshake_config: Option<HandshakeConfig>,
     ) -> Result<MidHandshake<Self>> {
         if request.method() != http::Method::GET {
             return Err(Error::Protocol(ProtocolError::WrongHttpMethod));
@@ -61,12 +75,17 @@ impl<S: Read + Write> ClientHandshake<S> {
         let (request, key) = generate_request(request)?;
 
         let machine = HandshakeMachine::start_write(stream, request);
+        let handshake_config = handshake_config.unwrap_or_default();
 
         let client = {
             let accept_key = derive_accept_key(key.as_ref());
             ClientHandshake {
                 verify_data: VerifyData { accept_key, subprotocols },
                 config,
+                handshake_config,
+                deadline: handshake_config
+                    .timeout
+                    .and_then(|timeout| Instant::now().checked_add(timeout)),
                 _marker: PhantomData,
             }
         };
@@ -85,9 +104,9 @@ impl<S: Read + Write> HandshakeRole for ClientHandshake<S> {
         finish: StageResult<Self::IncomingData, Self::InternalStream>,
     ) -> Result<ProcessingResult<Self::InternalStream, Self::FinalResult>> {
         Ok(match finish {
-            StageResult::DoneWriting(stream) => {
-                ProcessingResult::Continue(HandshakeMachine::start_read(stream))
-            }
+            StageResult::DoneWriting(stream) => ProcessingResult::Continue(
+                HandshakeMachine::start_read_with_config(stream, self.handshake_config),
+            ),
             StageResult::DoneReading { stream, result, tail } => {
                 let result = match self.verify_data.verify_response(result) {
                     Ok(r) => r,
@@ -105,6 +124,10 @@ impl<S: Read + Write> HandshakeRole for ClientHandshake<S> {
             }
         })
     }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
 }
 
 /// Verifies and generates a client WebSocket request from the original request and extracts a WebSocket key from it.
@@ -184,7 +207,7 @@ pub fn generate_request(mut request: Request) -> Result<(Vec<u8>, String)> {
     Ok((req, key))
 }
 
-fn extract_subprotocols_from_request(request: &Request) -> Result<Option<Vec<String>>> {
+pub(super) fn extract_subprotocols_from_request(request: &Request) -> Result<Option<Vec<String>>> {
     if let Some(subprotocols) = request.headers().get("Sec-WebSocket-Protocol") {
         Ok(Some(subprotocols.to_str()?.split(",").map(|s| s.to_string()).collect()))
     } else {
@@ -255,39 +278,56 @@ impl VerifyData {
         // not present in the client's handshake (the server has indicated a
         // subprotocol not requested by the client), the client MUST _Fail
         // the WebSocket Connection_. (RFC 6455)
-        if headers.get("Sec-WebSocket-Protocol").is_none() && self.subprotocols.is_some() {
-            return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                SubProtocolError::NoSubProtocol,
-            )));
-        }
+        verify_subprotocol(headers, &self.subprotocols)?;
 
-        if headers.get("Sec-WebSocket-Protocol").is_some() && self.subprotocols.is_none() {
-            return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                SubProtocolError::ServerSentSubProtocolNoneRequested,
-            )));
-        }
+        Ok(response)
+    }
+}
 
-        if let Some(returned_subprotocol) = headers.get("Sec-WebSocket-Protocol") {
-            if let Some(accepted_subprotocols) = &self.subprotocols {
-                if !accepted_subprotocols.contains(&returned_subprotocol.to_str()?.to_string()) {
-                    return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                        SubProtocolError::InvalidSubProtocol,
-                    )));
-                }
+/// Check the `Sec-WebSocket-Protocol` header of a response against the requested subprotocols.
+pub(super) fn verify_subprotocol(
+    headers: &HeaderMap,
+    subprotocols: &Option<Vec<String>>,
+) -> Result<()> {
+    if headers.get("Sec-WebSocket-Protocol").is_none() && subprotocols.is_some() {
+        return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
+            SubProtocolError::NoSubProtocol,
+        )));
+    }
+
+    if headers.get("Sec-WebSocket-Protocol").is_some() && subprotocols.is_none() {
+        return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
+            SubProtocolError::ServerSentSubProtocolNoneRequested,
+        )));
+    }
+
+    if let Some(returned_subprotocol) = headers.get("Sec-WebSocket-Protocol") {
+        if let Some(accepted_subprotocols) = subprotocols {
+            if !accepted_subprotocols.contains(&returned_subprotocol.to_str()?.to_string()) {
+                return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
+                    SubProtocolError::InvalidSubProtocol,
+                )));
             }
         }
-
-        Ok(response)
     }
+
+    Ok(())
 }
 
 impl TryParse for Response {
-    fn try_parse(buf: &[u8]) -> Result<Option<(usize, Self)>> {
-        let mut hbuffer = [httparse::EMPTY_HEADER; MAX_HEADERS];
-        let mut req = httparse::Response::new(&mut hbuffer);
-        Ok(match req.parse(buf)? {
-            Status::Partial => None,
-            Status::Complete(size) => Some((size, Response::from_httparse(req)?)),
+    fn try_parse_with_config(
+        buf: &[u8],
+        config: &HandshakeConfig,
+    ) -> Result<Option<(usize, Self)>> {
+        with_header_buffer(config, |hbuffer| {
+            let mut req = httparse::Response::new(hbuffer);
+            Ok(match req.parse(buf)? {
+                Status::Partial => None,
+                Status::Complete(size) => {
+                    check_header_lines(req.headers, config)?;
+                    Some((size, Response::from_httparse(req)?))
+                }
+            })
         })
     }
 }
@@ -3,12 +3,43 @@
 use http::header::{HeaderMap, HeaderName, HeaderValue};
 use httparse::Status;
 
-use super::machine::TryParse;
-use crate::error::Result;
+use super::{machine::TryParse, HandshakeConfig};
+use crate::error::{CapacityError, Result};
 
-/// Limit for the number of header lines.
+/// Default limit for the number of header lines, see [`HandshakeConfig::max_headers`].
 pub const MAX_HEADERS: usize = 124;
 
+/// Call `parse` with room for [`HandshakeConfig::max_headers`] header lines. The buffer lives on
+/// the stack unless more than [`MAX_HEADERS`] lines are allowed.
+pub(crate) fn with_header_buffer<'b, T>(
+    config: &HandshakeConfig,
+    parse: impl FnOnce(&mut [httparse::Header<'b>]) -> T,
+) -> T {
+    if config.max_headers <= MAX_HEADERS {
+        let mut hbuffer = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        parse(&mut hbuffer[..config.max_headers])
+    } else {
+        parse(&mut vec![httparse::EMPTY_HEADER; config.max_headers])
+    }
+}
+
+/// Check parsed header lines against [`HandshakeConfig::max_header_line_length`].
+pub(crate) fn check_header_lines(
+    headers: &[httparse::Header<'_>],
+    config: &HandshakeConfig,
+) -> Result<()> {
+    if let Some(max_size) = config.max_header_line_length {
+        for h in headers {
+            // "Name: value"
+            let size = h.name.len() + 2 + h.value.len();
+            if size > max_size {
+                return Err(CapacityError::HeaderTooLong { size, max_size }.into());
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Trait to convert raw objects into HTTP parseables.
 pub(crate) trait FromHttparse<T>: Sized {
     /// Convert raw object into parsed HTTP headers.
@@ -29,11 +60,18 @@ impl<'b: 'h, 'h> FromHttparse<&'b [httparse::Header<'h>]> for HeaderMap {
     }
 }
 impl TryParse for HeaderMap {
-    fn try_parse(buf: &[u8]) -> Result<Option<(usize, Self)>> {
-        let mut hbuffer = [httparse::EMPTY_HEADER; MAX_HEADERS];
-        Ok(match httparse::parse_headers(buf, &mut hbuffer)? {
-            Status::Partial => None,
-            Status::Complete((size, hdr)) => Some((size, HeaderMap::from_httparse(hdr)?)),
+    fn try_parse_with_config(
+        buf: &[u8],
+        config: &HandshakeConfig,
+    ) -> Result<Option<(usize, Self)>> {
+        with_header_buffer(config, |hbuffer| {
+            Ok(match httparse::parse_headers(buf, hbuffer)? {
+                Status::Partial => None,
+                Status::Complete((size, hdr)) => {
+                    check_header_lines(hdr, config)?;
+                    Some((size, HeaderMap::from_httparse(hdr)?))
+                }
+            })
         })
     }
 }
@@ -41,7 +79,11 @@ impl TryParse for HeaderMap {
 #[cfg(test)]
 mod tests {
 
-    use super::{super::machine::TryParse, HeaderMap};
+    use super::{
+        super::{machine::TryParse, HandshakeConfig},
+        HeaderMap, MAX_HEADERS,
+    };
+    use crate::error::{CapacityError, Error};
 
     #[test]
     fn headers() {
@@ -78,4 +120,44 @@ mod tests {
         let hdr = HeaderMap::try_parse(DATA).unwrap();
         assert!(hdr.is_none());
     }
+
+    #[test]
+    fn headers_limits() {
+        const DATA: &[u8] = b"Host: foo.com\r\n\
+              Cookie: 0123456789abcdef\r\n\
+              Upgrade: websocket\r\n\
+              \r\n";
+        let config = HandshakeConfig { max_headers: 2, ..HandshakeConfig::default() };
+        assert!(matches!(
+            HeaderMap::try_parse_with_config(DATA, &config),
+            Err(Error::Capacity(CapacityError::TooManyHeaders))
+        ));
+
+        let config =
+            HandshakeConfig { max_header_line_length: Some(20), ..HandshakeConfig::default() };
+        assert!(matches!(
+            HeaderMap::try_parse_with_config(DATA, &config),
+            Err(Error::Capacity(CapacityError::HeaderTooLong { size: 24, max_size: 20 }))
+        ));
+
+        let config =
+            HandshakeConfig { max_header_line_length: Some(24), ..HandshakeConfig::default() };
+        assert!(HeaderMap::try_parse_with_config(DATA, &config).unwrap().is_some());
+    }
+
+    #[test]
+    fn more_headers_than_default() {
+        let mut data: Vec<u8> = (0..MAX_HEADERS + 1)
+            .flat_map(|i| format!("X-Header-{i}: {i}\r\n").into_bytes())
+            .collect();
+        data.extend_from_slice(b"\r\n");
+        assert!(matches!(
+            HeaderMap::try_parse(&data),
+            Err(Error::Capacity(CapacityError::TooManyHeaders))
+        ));
+
+        let config = HandshakeConfig::default().max_headers(MAX_HEADERS + 1);
+        let (_, hdr) = HeaderMap::try_parse_with_config(&data, &config).unwrap().unwrap();
+        assert_eq!(hdr.len(), MAX_HEADERS + 1);
+    }
 }
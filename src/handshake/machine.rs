@@ -4,6 +4,7 @@ use bytes::Buf;
 use log::*;
 use std::io::{Cursor, Read, Write};
 
+use super::HandshakeConfig;
 use crate::{
     error::{Error, ProtocolError, Result},
     util::NonBlockingResult,
@@ -15,16 +16,29 @@ use crate::{
 pub struct HandshakeMachine<Stream> {
     stream: Stream,
     state: HandshakeState,
+    config: HandshakeConfig,
 }
 
 impl<Stream> HandshakeMachine<Stream> {
     /// Start reading data from the peer.
     pub fn start_read(stream: Stream) -> Self {
-        Self { stream, state: HandshakeState::Reading(ReadBuffer::new(), AttackCheck::new()) }
+        Self::start_read_with_config(stream, HandshakeConfig::default())
+    }
+    /// Start reading data from the peer, enforcing the limits of the given configuration.
+    pub fn start_read_with_config(stream: Stream, config: HandshakeConfig) -> Self {
+        Self {
+            stream,
+            state: HandshakeState::Reading(ReadBuffer::new(), AttackCheck::new()),
+            config,
+        }
     }
     /// Start writing data to the peer.
     pub fn start_write<D: Into<Vec<u8>>>(stream: Stream, data: D) -> Self {
-        HandshakeMachine { stream, state: HandshakeState::Writing(Cursor::new(data.into())) }
+        HandshakeMachine {
+            stream,
+            state: HandshakeState::Writing(Cursor::new(data.into())),
+            config: HandshakeConfig::default(),
+        }
     }
     /// Returns a shared reference to the inner stream.
     pub fn get_ref(&self) -> &Stream {
@@ -34,6 +48,14 @@ impl<Stream> HandshakeMachine<Stream> {
     pub fn get_mut(&mut self) -> &mut Stream {
         &mut self.stream
     }
+    /// Whether the machine is reading a request or response from the peer.
+    pub fn wants_read(&self) -> bool {
+        matches!(self.state, HandshakeState::Reading(..))
+    }
+    /// Whether the machine is writing a request or response to the peer.
+    pub fn wants_write(&self) -> bool {
+        matches!(self.state, HandshakeState::Writing(_))
+    }
 }
 
 impl<Stream: Read + Write> HandshakeMachine<Stream> {
@@ -46,23 +68,27 @@ impl<Stream: Read + Write> HandshakeMachine<Stream> {
                 match read {
                     Some(0) => Err(Error::Protocol(ProtocolError::HandshakeIncomplete)),
                     Some(count) => {
-                        attack_check.check_incoming_packet_size(count)?;
+                        attack_check.check_incoming_packet_size(count, &self.config)?;
                         // TODO: this is slow for big headers with too many small packets.
                         // The parser has to be reworked in order to work on streams instead
                         // of buffers.
-                        Ok(if let Some((size, obj)) = Obj::try_parse(Buf::chunk(&buf))? {
-                            buf.advance(size);
-                            RoundResult::StageFinished(StageResult::DoneReading {
-                                result: obj,
-                                stream: self.stream,
-                                tail: buf.into_vec(),
-                            })
-                        } else {
-                            RoundResult::Incomplete(HandshakeMachine {
-                                state: HandshakeState::Reading(buf, attack_check),
-                                ..self
-                            })
-                        })
+                        Ok(
+                            if let Some((size, obj)) =
+                                Obj::try_parse_with_config(Buf::chunk(&buf), &self.config)?
+                            {
+                                buf.advance(size);
+                                RoundResult::StageFinished(StageResult::DoneReading {
+                                    result: obj,
+                                    stream: self.stream,
+                                    tail: buf.into_vec(),
+                                })
+                            } else {
+                                RoundResult::Incomplete(HandshakeMachine {
+                                    state: HandshakeState::Reading(buf, attack_check),
+                                    ..self
+                                })
+                            },
+                        )
                     }
                     None => Ok(RoundResult::WouldBlock(HandshakeMachine {
                         state: HandshakeState::Reading(buf, attack_check),
@@ -117,8 +143,17 @@ pub enum StageResult<Obj, Stream> {
 
 /// The parseable object.
 pub trait TryParse: Sized {
+    /// Return Ok(None) if incomplete, Err on syntax error or if the limits of `config` are
+    /// exceeded.
+    fn try_parse_with_config(
+        data: &[u8],
+        config: &HandshakeConfig,
+    ) -> Result<Option<(usize, Self)>>;
+
     /// Return Ok(None) if incomplete, Err on syntax error.
-    fn try_parse(data: &[u8]) -> Result<Option<(usize, Self)>>;
+    fn try_parse(data: &[u8]) -> Result<Option<(usize, Self)>> {
+        Self::try_parse_with_config(data, &HandshakeConfig::default())
+    }
 }
 
 /// The handshake state.
@@ -148,27 +183,20 @@ impl AttackCheck {
 
     /// Check the size of an incoming packet. To be called immediately after `read()`
     /// passing its returned bytes count as `size`.
-    fn check_incoming_packet_size(&mut self, size: usize) -> Result<()> {
+    fn check_incoming_packet_size(&mut self, size: usize, config: &HandshakeConfig) -> Result<()> {
         self.number_of_packets += 1;
         self.number_of_bytes += size;
 
-        // TODO: these values are hardcoded. Instead of making them configurable,
-        // rework the way HTTP header is parsed to remove this check at all.
-        const MAX_BYTES: usize = 65536;
-        const MAX_PACKETS: usize = 512;
-        const MIN_PACKET_SIZE: usize = 128;
-        const MIN_PACKET_CHECK_THRESHOLD: usize = 64;
-
-        if self.number_of_bytes > MAX_BYTES {
+        if self.number_of_bytes > config.max_header_bytes {
             return Err(Error::AttackAttempt);
         }
 
-        if self.number_of_packets > MAX_PACKETS {
+        if self.number_of_packets > config.max_packets {
             return Err(Error::AttackAttempt);
         }
 
-        if self.number_of_packets > MIN_PACKET_CHECK_THRESHOLD
-            && self.number_of_packets * MIN_PACKET_SIZE > self.number_of_bytes
+        if self.number_of_packets > config.min_packet_check_threshold
+            && self.number_of_packets.saturating_mul(config.min_packet_size) > self.number_of_bytes
         {
             return Err(Error::AttackAttempt);
         }
@@ -176,3 +204,47 @@ impl AttackCheck {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AttackCheck, HandshakeConfig, HandshakeMachine};
+    use crate::error::Error;
+
+    #[test]
+    fn attack_check_default_limits() {
+        let config = HandshakeConfig::default();
+        let mut check = AttackCheck::new();
+        assert!(check.check_incoming_packet_size(4096, &config).is_ok());
+        for _ in 0..15 {
+            assert!(check.check_incoming_packet_size(4096, &config).is_ok());
+        }
+        assert!(matches!(check.check_incoming_packet_size(1, &config), Err(Error::AttackAttempt)));
+    }
+
+    #[test]
+    fn attack_check_custom_limits() {
+        let config = HandshakeConfig {
+            max_header_bytes: 256 << 10,
+            min_packet_size: 1,
+            ..HandshakeConfig::default()
+        };
+        let mut check = AttackCheck::new();
+        for _ in 0..64 {
+            assert!(check.check_incoming_packet_size(4096, &config).is_ok());
+        }
+
+        let config = HandshakeConfig { max_packets: 2, ..HandshakeConfig::default() };
+        let mut check = AttackCheck::new();
+        assert!(check.check_incoming_packet_size(10, &config).is_ok());
+        assert!(check.check_incoming_packet_size(10, &config).is_ok());
+        assert!(matches!(check.check_incoming_packet_size(10, &config), Err(Error::AttackAttempt)));
+    }
+
+    #[test]
+    fn readiness_interest() {
+        let machine = HandshakeMachine::start_read(());
+        assert!(machine.wants_read() && !machine.wants_write());
+        let machine = HandshakeMachine::start_write((), "GET / HTTP/1.1\r\n\r\n");
+        assert!(machine.wants_write() && !machine.wants_read());
+    }
+}
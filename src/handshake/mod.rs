@@ -1,6 +1,7 @@
 //! WebSocket handshake control.
 
 pub mod client;
+pub mod extended_connect;
 pub mod headers;
 pub mod machine;
 pub mod server;
@@ -9,12 +10,124 @@ use std::{
     error::Error as ErrorTrait,
     fmt,
     io::{Read, Write},
+    time::{Duration, Instant},
 };
 
+use log::*;
 use sha1::{Digest, Sha1};
 
-use self::machine::{HandshakeMachine, RoundResult, StageResult, TryParse};
-use crate::error::Error;
+use self::{
+    headers::MAX_HEADERS,
+    machine::{HandshakeMachine, RoundResult, StageResult, TryParse},
+};
+use crate::error::{Error, ProtocolError};
+
+/// The configuration for the HTTP part of the WebSocket handshake.
+///
+/// These limits protect the peer reading the HTTP request (or response) from resource exhaustion.
+/// The defaults are suitable for most applications, but some clients send unusually large
+/// headers (e.g. cookies) while other deployments need much tighter limits.
+///
+/// ```
+/// use std::time::Duration;
+/// use tungstenite::handshake::HandshakeConfig;
+///
+/// let config = HandshakeConfig::default()
+///     .max_header_bytes(16 << 10)
+///     .timeout(Some(Duration::from_secs(10)));
+/// ```
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct HandshakeConfig {
+    /// The maximum total size of the HTTP request or response head in bytes.
+    /// The default value is 64 KiB.
+    pub max_header_bytes: usize,
+    /// The maximum number of header lines. The default value is [`MAX_HEADERS`].
+    pub max_headers: usize,
+    /// The maximum number of successful reads (typically TCP packets) it may take to receive
+    /// the HTTP head. The default value is 512.
+    pub max_packets: usize,
+    /// The minimum average size of the received packets, enforced once more than
+    /// [`min_packet_check_threshold`](Self::min_packet_check_threshold) packets were received.
+    /// This rejects peers that trickle the header in tiny pieces. The default value is 128.
+    pub min_packet_size: usize,
+    /// The number of packets after which [`min_packet_size`](Self::min_packet_size) is enforced.
+    /// The default value is 64.
+    pub min_packet_check_threshold: usize,
+    /// The maximum length of a single header line (name, separator and value) in bytes.
+    /// `None` means no limit other than [`max_header_bytes`](Self::max_header_bytes),
+    /// which is the default.
+    pub max_header_line_length: Option<usize>,
+    /// The time the whole handshake may take, measured from the moment it is started.
+    /// Exceeding it fails the handshake with [`ProtocolError::HandshakeTimedOut`].
+    /// `None` means no deadline, which is the default, as does a timeout too large to compute
+    /// the deadline.
+    ///
+    /// The deadline is checked whenever the handshake is driven, so it can't interrupt a
+    /// blocking read or write. With blocking streams, set the socket read and write timeouts
+    /// as well (e.g. [`TcpStream::set_read_timeout`](std::net::TcpStream::set_read_timeout)),
+    /// and with non-blocking streams use [`MidHandshake::remaining_time`] to schedule the
+    /// next attempt.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        HandshakeConfig {
+            max_header_bytes: 64 << 10,
+            max_headers: MAX_HEADERS,
+            max_packets: 512,
+            min_packet_size: 128,
+            min_packet_check_threshold: 64,
+            max_header_line_length: None,
+            timeout: None,
+        }
+    }
+}
+
+impl HandshakeConfig {
+    /// Set [`Self::max_header_bytes`].
+    pub fn max_header_bytes(mut self, max_header_bytes: usize) -> Self {
+        self.max_header_bytes = max_header_bytes;
+        self
+    }
+
+    /// Set [`Self::max_headers`].
+    pub fn max_headers(mut self, max_headers: usize) -> Self {
+        self.max_headers = max_headers;
+        self
+    }
+
+    /// Set [`Self::max_packets`].
+    pub fn max_packets(mut self, max_packets: usize) -> Self {
+        self.max_packets = max_packets;
+        self
+    }
+
+    /// Set [`Self::min_packet_size`].
+    pub fn min_packet_size(mut self, min_packet_size: usize) -> Self {
+        self.min_packet_size = min_packet_size;
+        self
+    }
+
+    /// Set [`Self::min_packet_check_threshold`].
+    pub fn min_packet_check_threshold(mut self, min_packet_check_threshold: usize) -> Self {
+        self.min_packet_check_threshold = min_packet_check_threshold;
+        self
+    }
+
+    /// Set [`Self::max_header_line_length`].
+    pub fn max_header_line_length(mut self, max_header_line_length: Option<usize>) -> Self {
+        self.max_header_line_length = max_header_line_length;
+        self
+    }
+
+    /// Set [`Self::timeout`].
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
 
 /// A WebSocket handshake.
 #[derive(Debug)]
@@ -34,10 +147,37 @@ impl<Role: HandshakeRole> MidHandshake<Role> {
         &mut self.machine
     }
 
+    /// Check if the stream should be polled for read readiness to continue the handshake.
+    pub fn wants_read(&self) -> bool {
+        self.machine.wants_read()
+    }
+
+    /// Check if the stream should be polled for write readiness to continue the handshake.
+    ///
+    /// Exactly one of [`wants_read`](Self::wants_read) and `wants_write` is true while the
+    /// handshake is interrupted.
+    pub fn wants_write(&self) -> bool {
+        self.machine.wants_write()
+    }
+
+    /// Returns the time left until the handshake deadline, if there is one.
+    ///
+    /// Returns `Some(Duration::ZERO)` once the deadline has passed; the next call to
+    /// [`handshake`](Self::handshake) will then fail.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.role.deadline().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
     /// Restarts the handshake process.
     pub fn handshake(mut self) -> Result<Role::FinalResult, HandshakeError<Role>> {
         let mut mach = self.machine;
         loop {
+            if self.role.deadline().map_or(false, |deadline| Instant::now() >= deadline) {
+                debug!("Handshake deadline exceeded.");
+                return Err(HandshakeError::Failure(Error::Protocol(
+                    ProtocolError::HandshakeTimedOut,
+                )));
+            }
             mach = match mach.single_round()? {
                 RoundResult::WouldBlock(m) => {
                     return Err(HandshakeError::Interrupted(MidHandshake { machine: m, ..self }))
@@ -99,6 +239,10 @@ pub trait HandshakeRole {
         &mut self,
         finish: StageResult<Self::IncomingData, Self::InternalStream>,
     ) -> Result<ProcessingResult<Self::InternalStream, Self::FinalResult>, Error>;
+    #[doc(hidden)]
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
 }
 
 /// Stage processing result.
@@ -125,11 +269,81 @@ pub fn derive_accept_key(request_key: &[u8]) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::derive_accept_key;
+    use super::{
+        derive_accept_key,
+        server::{NoCallback, ServerHandshake},
+        HandshakeConfig, HandshakeError,
+    };
+    use crate::error::{Error, ProtocolError};
+
+    use std::{
+        io::{self, Read, Write},
+        time::Duration,
+    };
+
+    #[derive(Debug)]
+    struct BlockingStream;
+
+    impl Read for BlockingStream {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl Write for BlockingStream {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 
     #[test]
     fn key_conversion() {
         // example from RFC 6455
         assert_eq!(derive_accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
     }
+
+    #[test]
+    fn handshake_deadline() {
+        let mid = ServerHandshake::start(BlockingStream, NoCallback, None);
+        assert_eq!(mid.remaining_time(), None);
+
+        let config = HandshakeConfig { timeout: Some(Duration::from_secs(60)), ..<_>::default() };
+        let mid = ServerHandshake::start_with_handshake_config(
+            BlockingStream,
+            NoCallback,
+            None,
+            Some(config),
+        );
+        let mid = match mid.handshake() {
+            Err(HandshakeError::Interrupted(mid)) => mid,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        assert!(mid.remaining_time().unwrap() > Duration::from_secs(30));
+
+        // Too far in the future to have a deadline.
+        let config = HandshakeConfig { timeout: Some(Duration::MAX), ..<_>::default() };
+        let mid = ServerHandshake::start_with_handshake_config(
+            BlockingStream,
+            NoCallback,
+            None,
+            Some(config),
+        );
+        assert_eq!(mid.remaining_time(), None);
+
+        let config = HandshakeConfig { timeout: Some(Duration::ZERO), ..<_>::default() };
+        let mid = ServerHandshake::start_with_handshake_config(
+            BlockingStream,
+            NoCallback,
+            None,
+            Some(config),
+        );
+        assert_eq!(mid.remaining_time(), Some(Duration::ZERO));
+        assert!(matches!(
+            mid.handshake(),
+            Err(HandshakeError::Failure(Error::Protocol(ProtocolError::HandshakeTimedOut)))
+        ));
+    }
 }
@@ -4,6 +4,7 @@ use std::{
     io::{self, Read, Write},
     marker::PhantomData,
     result::Result as StdResult,
+    time::Instant,
 };
 
 use http::{
@@ -14,9 +15,9 @@ use log::*;
 
 use super::{
     derive_accept_key,
-    headers::{FromHttparse, MAX_HEADERS},
+    headers::{check_header_lines, with_header_buffer, FromHttparse},
     machine::{HandshakeMachine, StageResult, TryParse},
-    HandshakeRole, MidHandshake, ProcessingResult,
+    HandshakeConfig, HandshakeRole, MidHandshake, ProcessingResult,
 };
 use crate::{
     error::{Error, ProtocolError, Result},
@@ -112,12 +113,19 @@ pub fn write_response<T>(mut w: impl io::Write, response: &HttpResponse<T>) -> R
 }
 
 impl TryParse for Request {
-    fn try_parse(buf: &[u8]) -> Result<Option<(usize, Self)>> {
-        let mut hbuffer = [httparse::EMPTY_HEADER; MAX_HEADERS];
-        let mut req = httparse::Request::new(&mut hbuffer);
-        Ok(match req.parse(buf)? {
-            Status::Partial => None,
-            Status::Complete(size) => Some((size, Request::from_httparse(req)?)),
+    fn try_parse_with_config(
+        buf: &[u8],
+        config: &HandshakeConfig,
+    ) -> Result<Option<(usize, Self)>> {
+        with_header_buffer(config, |hbuffer| {
+            let mut req = httparse::Request::new(hbuffer);
+            Ok(match req.parse(buf)? {
+                Status::Partial => None,
+                Status::Complete(size) => {
+                    check_header_lines(req.headers, config)?;
+                    Some((size, Request::from_httparse(req)?))
+                }
+            })
         })
     }
 }
@@ -202,6 +210,10 @@ pub struct ServerHandshake<S, C> {
     config: Option<WebSocketConfig>,
     /// Error code/flag. If set, an error will be returned after sending response to the client.
     error_response: Option<ErrorResponse>,
+    /// The point in time by which the handshake must be complete.
+    deadline: Option<Instant>,
+    /// Data the client sent after its request without waiting for the response.
+    tail: Vec<u8>,
     /// Internal stream type.
     _marker: PhantomData<S>,
 }
@@ -212,13 +224,29 @@ impl<S: Read + Write, C: Callback> ServerHandshake<S, C> {
     /// server, you can specify the callback if you want to add additional header to the client
     /// upon join based on the incoming headers.
     pub fn start(stream: S, callback: C, config: Option<WebSocketConfig>) -> MidHandshake<Self> {
+        Self::start_with_handshake_config(stream, callback, config, None)
+    }
+
+    /// Start server handshake with limits for reading the HTTP request. Passing `None` as
+    /// `handshake_config` is equal to calling [`start()`](Self::start).
+    pub fn start_with_handshake_config(
+        stream: S,
+        callback: C,
+        config: Option<WebSocketConfig>,
+        handshake_config: Option<HandshakeConfig>,
+    ) -> MidHandshake<Self> {
         trace!("Server handshake initiated.");
+        let handshake_config = handshake_config.unwrap_or_default();
         MidHandshake {
-            machine: HandshakeMachine::start_read(stream),
+            machine: HandshakeMachine::start_read_with_config(stream, handshake_config),
             role: ServerHandshake {
                 callback: Some(callback),
                 config,
                 error_response: None,
+                deadline: handshake_config
+                    .timeout
+                    .and_then(|timeout| Instant::now().checked_add(timeout)),
+                tail: Vec::new(),
                 _marker: PhantomData,
             },
         }
@@ -236,9 +264,9 @@ impl<S: Read + Write, C: Callback> HandshakeRole for ServerHandshake<S, C> {
     ) -> Result<ProcessingResult<Self::InternalStream, Self::FinalResult>> {
         Ok(match finish {
             StageResult::DoneReading { stream, result, tail } => {
-                if !tail.is_empty() {
-                    return Err(Error::Protocol(ProtocolError::JunkAfterRequest));
-                }
+                // Clients may pipeline their first frames right after the request, keep them
+                // for the frame codec.
+                self.tail = tail;
 
                 let response = create_response(&result)?;
                 let callback_result = if let Some(callback) = self.callback.take() {
@@ -283,17 +311,53 @@ impl<S: Read + Write, C: Callback> HandshakeRole for ServerHandshake<S, C> {
                     return Err(Error::Http(http::Response::from_parts(parts, body)));
                 } else {
                     debug!("Server handshake done.");
-                    let websocket = WebSocket::from_raw_socket(stream, Role::Server, self.config);
+                    let tail = std::mem::take(&mut self.tail);
+                    let websocket =
+                        WebSocket::from_partially_read(stream, tail, Role::Server, self.config);
                     ProcessingResult::Done(websocket)
                 }
             }
         })
     }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{super::machine::TryParse, create_response, Request};
+    use super::{
+        super::{machine::TryParse, HandshakeConfig},
+        create_response, NoCallback, Request, ServerHandshake,
+    };
+    use crate::{
+        error::{CapacityError, Error},
+        Message,
+    };
+
+    use std::io::{self, Cursor, Read, Write};
+
+    #[derive(Debug)]
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 
     #[test]
     fn request_parsing() {
@@ -303,6 +367,20 @@ mod tests {
         assert_eq!(req.headers().get("Host").unwrap(), &b"foo.com"[..]);
     }
 
+    #[test]
+    fn request_parsing_with_config() {
+        const DATA: &[u8] = b"GET /script.ws HTTP/1.1\r\nHost: foo.com\r\nCookie: a=b\r\n\r\n";
+        let config = HandshakeConfig { max_headers: 1, ..HandshakeConfig::default() };
+        assert!(matches!(
+            Request::try_parse_with_config(DATA, &config),
+            Err(Error::Capacity(CapacityError::TooManyHeaders))
+        ));
+
+        let config = HandshakeConfig { max_headers: 2, ..HandshakeConfig::default() };
+        let (_, req) = Request::try_parse_with_config(DATA, &config).unwrap().unwrap();
+        assert_eq!(req.headers().get("Cookie").unwrap(), &b"a=b"[..]);
+    }
+
     #[test]
     fn request_replying() {
         const DATA: &[u8] = b"\
@@ -321,4 +399,24 @@ mod tests {
             b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".as_ref()
         );
     }
+
+    #[test]
+    fn pipelined_frames_after_request() {
+        let mut input = b"\
+            GET /script.ws HTTP/1.1\r\n\
+            Host: foo.com\r\n\
+            Connection: upgrade\r\n\
+            Upgrade: websocket\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            \r\n"
+            .to_vec();
+        // Masked text frame "Hi" sent without waiting for the response.
+        input.extend_from_slice(&[0x81, 0x82, 0x01, 0x02, 0x03, 0x04, b'H' ^ 0x01, b'i' ^ 0x02]);
+
+        let stream = MockStream { input: Cursor::new(input), output: Vec::new() };
+        let mut ws = ServerHandshake::start(stream, NoCallback, None).handshake().unwrap();
+        assert!(ws.get_ref().output.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+        assert_eq!(ws.read().unwrap(), Message::Text("Hi".into()));
+    }
 }
@@ -0,0 +1,660 @@
+//! Topic-based fan-out of messages to many WebSocket subscribers.
+//!
+//! A [`Hub`] never writes to sockets itself: [`Hub::publish`] encodes a message once and
+//! appends it to the bounded queue of every subscriber of the topic, so a slow client can't
+//! block the publisher. Each [`Subscription`] then writes its queue to its socket, either from a
+//! thread per connection with [`Subscription::forward`], or from an event loop with
+//! [`Subscription::write_to`] whenever the socket is writable.
+//!
+//! When a queue is full, the [`SlowConsumerPolicy`] decides what happens to the new message.
+//! [`Subscription::metrics`] and [`Hub::metrics`] report how far subscribers lag behind.
+//!
+//! ```no_run
+//! use std::{net::TcpListener, thread::spawn};
+//! use tungstenite::{
+//!     accept,
+//!     hub::{Hub, HubConfig},
+//!     Message,
+//! };
+//!
+//! let hub = Hub::new(HubConfig::default());
+//! let server = TcpListener::bind("127.0.0.1:9001").unwrap();
+//! let subscriptions = hub.clone();
+//! spawn(move || {
+//!     for stream in server.incoming() {
+//!         let subscription = subscriptions.subscribe();
+//!         subscription.subscribe_to("prices");
+//!         spawn(move || {
+//!             let mut socket = accept(stream.unwrap()).unwrap();
+//!             subscription.forward(&mut socket).unwrap();
+//!         });
+//!     }
+//! });
+//! loop {
+//!     hub.publish("prices", Message::text("EUR/USD 1.08"));
+//! }
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    io::{ErrorKind, Read, Write},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use log::*;
+
+use crate::{
+    error::{Error, Result},
+    protocol::{frame::coding::CloseCode, CloseFrame, Message, PreparedMessage, WebSocket},
+};
+
+/// What happens to a message published to a subscriber whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowConsumerPolicy {
+    /// Drop the oldest queued message to make room for the new one.
+    DropOldest,
+    /// Drop the new message.
+    DropNewest,
+    /// Replace the latest queued message of the same topic with the new one, for topics where
+    /// only the latest value matters. Drops the oldest message if none of the topic is queued.
+    Coalesce,
+    /// Drop all queued messages and close the connection with [`CloseCode::Policy`].
+    Disconnect,
+}
+
+/// The configuration of a [`Hub`].
+#[derive(Debug, Clone, Copy)]
+pub struct HubConfig {
+    /// The maximum number of messages queued for a subscriber. The default value is 1024.
+    pub queue_capacity: usize,
+    /// What happens to messages published to a subscriber whose queue is full. The default is
+    /// [`SlowConsumerPolicy::DropOldest`].
+    pub policy: SlowConsumerPolicy,
+}
+
+impl Default for HubConfig {
+    fn default() -> Self {
+        HubConfig { queue_capacity: 1024, policy: SlowConsumerPolicy::DropOldest }
+    }
+}
+
+/// How far a subscriber lags behind the publisher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct SubscriberMetrics {
+    /// The identifier of the subscriber, see [`Subscription::id`].
+    pub id: u64,
+    /// The number of messages waiting in the queue.
+    pub queued: usize,
+    /// How long the oldest queued message has been waiting.
+    pub lag: Duration,
+    /// The number of messages delivered to the subscriber.
+    pub delivered: u64,
+    /// The number of messages dropped because the queue was full.
+    pub dropped: u64,
+    /// The number of queued messages replaced by newer ones with
+    /// [`SlowConsumerPolicy::Coalesce`].
+    pub coalesced: u64,
+    /// Whether the subscriber was disconnected, by [`SlowConsumerPolicy::Disconnect`] or
+    /// [`Hub::close`].
+    pub disconnected: bool,
+}
+
+struct Entry {
+    topic: Arc<str>,
+    message: PreparedMessage,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+struct QueueState {
+    entries: VecDeque<Entry>,
+    /// The close frame to send once the queue is drained.
+    close: Option<CloseFrame<'static>>,
+    delivered: u64,
+    dropped: u64,
+    coalesced: u64,
+}
+
+/// The queue of a subscriber, filled by the publisher and drained by the subscription.
+#[derive(Default)]
+struct Queue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Append a message according to the policy. Returns whether the message was queued.
+    fn push(&self, config: &HubConfig, topic: &Arc<str>, message: &PreparedMessage) -> bool {
+        let mut state = self.lock();
+        if state.close.is_some() {
+            return false;
+        }
+        let entry =
+            Entry { topic: topic.clone(), message: message.clone(), queued_at: Instant::now() };
+        if state.entries.len() < config.queue_capacity.max(1) {
+            state.entries.push_back(entry);
+            self.ready.notify_one();
+            return true;
+        }
+
+        match config.policy {
+            SlowConsumerPolicy::DropOldest => {
+                state.entries.pop_front();
+                state.entries.push_back(entry);
+                state.dropped += 1;
+            }
+            SlowConsumerPolicy::DropNewest => {
+                state.dropped += 1;
+                return false;
+            }
+            SlowConsumerPolicy::Coalesce => {
+                match state.entries.iter_mut().rev().find(|queued| queued.topic == *topic) {
+                    // Keep the queueing time, the topic has been waiting since then.
+                    Some(queued) => queued.message = entry.message,
+                    None => {
+                        state.entries.pop_front();
+                        state.entries.push_back(entry);
+                        state.dropped += 1;
+                        return true;
+                    }
+                }
+                state.coalesced += 1;
+            }
+            SlowConsumerPolicy::Disconnect => {
+                state.dropped += state.entries.len() as u64 + 1;
+                state.entries.clear();
+                state.close = Some(CloseFrame {
+                    code: CloseCode::Policy,
+                    reason: Cow::Borrowed("subscriber too slow"),
+                });
+                self.ready.notify_all();
+                return false;
+            }
+        }
+        true
+    }
+
+    fn close(&self, frame: CloseFrame<'static>) {
+        let mut state = self.lock();
+        if state.close.is_none() {
+            state.close = Some(frame);
+        }
+        self.ready.notify_all();
+    }
+}
+
+#[derive(Default)]
+struct Topics {
+    /// The subscribers of each topic.
+    topics: HashMap<Arc<str>, HashMap<u64, Arc<Queue>>>,
+    /// The queue and the topics of each subscriber.
+    subscribers: HashMap<u64, (Arc<Queue>, HashSet<Arc<str>>)>,
+    next_id: u64,
+    closed: bool,
+}
+
+struct Shared {
+    config: HubConfig,
+    topics: Mutex<Topics>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Topics> {
+        self.topics.lock().unwrap()
+    }
+}
+
+/// A topic-based fan-out hub, see the [module documentation](self).
+///
+/// Cloning is cheap, clones publish to the same subscribers.
+#[derive(Clone)]
+pub struct Hub {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for Hub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let topics = self.shared.lock();
+        f.debug_struct("Hub")
+            .field("config", &self.shared.config)
+            .field("topics", &topics.topics.len())
+            .field("subscribers", &topics.subscribers.len())
+            .finish()
+    }
+}
+
+impl Hub {
+    /// Create a hub without subscribers.
+    pub fn new(config: HubConfig) -> Self {
+        Hub { shared: Arc::new(Shared { config, topics: Mutex::default() }) }
+    }
+
+    /// Add a subscriber, not subscribed to any topic yet.
+    ///
+    /// The subscriber is removed when the returned subscription is dropped. After
+    /// [`close`](Self::close), new subscriptions are disconnected immediately.
+    pub fn subscribe(&self) -> Subscription {
+        let queue = Arc::new(Queue::default());
+        let mut topics = self.shared.lock();
+        let id = topics.next_id;
+        topics.next_id += 1;
+        if topics.closed {
+            queue.close(away());
+        }
+        topics.subscribers.insert(id, (queue.clone(), HashSet::new()));
+        Subscription { id, queue, shared: self.shared.clone() }
+    }
+
+    /// Queue a message for all subscribers of `topic`. Returns the number of subscribers the
+    /// message was queued for.
+    pub fn publish(&self, topic: &str, message: Message) -> usize {
+        self.publish_prepared(topic, &PreparedMessage::new(message))
+    }
+
+    /// Queue a message encoded in advance for all subscribers of `topic`. Returns the number of
+    /// subscribers the message was queued for.
+    pub fn publish_prepared(&self, topic: &str, message: &PreparedMessage) -> usize {
+        let (topic, queues) = {
+            let topics = self.shared.lock();
+            match topics.topics.get_key_value(topic) {
+                Some((topic, queues)) => {
+                    (topic.clone(), queues.values().cloned().collect::<Vec<_>>())
+                }
+                None => return 0,
+            }
+        };
+        let config = &self.shared.config;
+        queues.iter().filter(|queue| queue.push(config, &topic, message)).count()
+    }
+
+    /// The number of subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.shared.lock().subscribers.len()
+    }
+
+    /// The metrics of all subscribers, ordered by their identifiers.
+    pub fn metrics(&self) -> Vec<SubscriberMetrics> {
+        let queues: Vec<_> = {
+            let topics = self.shared.lock();
+            topics.subscribers.iter().map(|(id, (queue, _))| (*id, queue.clone())).collect()
+        };
+        let mut metrics: Vec<_> = queues.iter().map(|(id, queue)| metrics(*id, queue)).collect();
+        metrics.sort_by_key(|m| m.id);
+        metrics
+    }
+
+    /// Disconnect all subscribers with [`CloseCode::Away`] after delivering the messages queued
+    /// for them, and stop accepting new ones.
+    pub fn close(&self) {
+        let mut topics = self.shared.lock();
+        topics.closed = true;
+        for (queue, _) in topics.subscribers.values() {
+            queue.close(away());
+        }
+    }
+}
+
+fn away() -> CloseFrame<'static> {
+    CloseFrame { code: CloseCode::Away, reason: Cow::Borrowed("hub closed") }
+}
+
+fn metrics(id: u64, queue: &Queue) -> SubscriberMetrics {
+    let state = queue.lock();
+    SubscriberMetrics {
+        id,
+        queued: state.entries.len(),
+        lag: state.entries.front().map_or(Duration::ZERO, |entry| entry.queued_at.elapsed()),
+        delivered: state.delivered,
+        dropped: state.dropped,
+        coalesced: state.coalesced,
+        disconnected: state.close.is_some(),
+    }
+}
+
+/// A subscriber of a [`Hub`], created by [`Hub::subscribe`].
+///
+/// Messages are taken from the queue with [`recv`](Self::recv) and friends, or written to a
+/// socket with [`forward`](Self::forward) or [`write_to`](Self::write_to).
+pub struct Subscription {
+    id: u64,
+    queue: Arc<Queue>,
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+impl Subscription {
+    /// The identifier of the subscriber, unique within its hub.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Receive the messages published to `topic` from now on.
+    pub fn subscribe_to(&self, topic: &str) {
+        let mut guard = self.shared.lock();
+        let topics = &mut *guard;
+        let topic = match topics.topics.get_key_value(topic) {
+            Some((topic, _)) => topic.clone(),
+            None => Arc::from(topic),
+        };
+        topics.topics.entry(topic.clone()).or_default().insert(self.id, self.queue.clone());
+        if let Some((_, subscribed)) = topics.subscribers.get_mut(&self.id) {
+            subscribed.insert(topic);
+        }
+    }
+
+    /// Stop receiving messages published to `topic`. Messages already queued are still
+    /// delivered.
+    pub fn unsubscribe_from(&self, topic: &str) {
+        let mut topics = self.shared.lock();
+        remove_from_topic(&mut topics.topics, topic, self.id);
+        if let Some((_, subscribed)) = topics.subscribers.get_mut(&self.id) {
+            subscribed.remove(topic);
+        }
+    }
+
+    /// The next queued message, waiting for one to be published. Returns `None` once the
+    /// subscriber was disconnected and all remaining messages were received.
+    pub fn recv(&self) -> Option<PreparedMessage> {
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(message) = take(&mut state) {
+                return Some(message);
+            }
+            if state.close.is_some() {
+                return None;
+            }
+            state = self.queue.ready.wait(state).unwrap();
+        }
+    }
+
+    /// Like [`recv`](Self::recv), but waits at most `timeout` for a message to be published.
+    /// A timeout too large to compute the deadline waits like [`recv`](Self::recv).
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<PreparedMessage> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(message) = take(&mut state) {
+                return Some(message);
+            }
+            let now = Instant::now();
+            if state.close.is_some() || deadline.map_or(false, |deadline| now >= deadline) {
+                return None;
+            }
+            state = match deadline {
+                Some(deadline) => self.queue.ready.wait_timeout(state, deadline - now).unwrap().0,
+                None => self.queue.ready.wait(state).unwrap(),
+            };
+        }
+    }
+
+    /// The next queued message, if any, without waiting.
+    pub fn try_recv(&self) -> Option<PreparedMessage> {
+        take(&mut self.queue.lock())
+    }
+
+    /// Whether the subscriber was disconnected, by [`SlowConsumerPolicy::Disconnect`] or
+    /// [`Hub::close`]. Remaining messages can still be received.
+    pub fn is_disconnected(&self) -> bool {
+        self.queue.lock().close.is_some()
+    }
+
+    /// How far the subscriber lags behind the publisher.
+    pub fn metrics(&self) -> SubscriberMetrics {
+        metrics(self.id, &self.queue)
+    }
+
+    /// Write the queued messages to a server socket until the subscriber is disconnected, then
+    /// close the connection.
+    ///
+    /// Meant for a blocking socket with a thread per connection. The messages published in the
+    /// meantime are written at once and flushed together. Replies to pings and close frames of the
+    /// client are only sent if the socket is read as well, e.g. from another thread through a
+    /// clone of the stream.
+    ///
+    /// After closing, read the socket until [`Error::ConnectionClosed`] to complete the close
+    /// handshake.
+    pub fn forward<S: Read + Write>(&self, socket: &mut WebSocket<S>) -> Result<()> {
+        while let Some(message) = self.recv() {
+            socket.write_prepared(&message)?;
+            while let Some(message) = self.try_recv() {
+                socket.write_prepared(&message)?;
+            }
+            socket.flush()?;
+        }
+        let close = self.queue.lock().close.clone();
+        socket.close(close)
+    }
+
+    /// Write as many queued messages to a non-blocking server socket as it accepts without
+    /// blocking, and close the connection once the subscriber is disconnected and the queue is
+    /// drained.
+    ///
+    /// Call it again when more messages were published or the socket is writable again. Messages
+    /// stay queued while the write buffer of the socket is full, see
+    /// [`WebSocketConfig::max_write_buffer_size`](crate::protocol::WebSocketConfig::max_write_buffer_size).
+    pub fn write_to<S: Read + Write>(&self, socket: &mut WebSocket<S>) -> Result<()> {
+        loop {
+            let entry = match self.queue.lock().entries.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            match socket.write_prepared(&entry.message) {
+                Ok(()) => self.queue.lock().delivered += 1,
+                // The message was buffered but the socket can't take more.
+                Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                    self.queue.lock().delivered += 1;
+                    return Ok(());
+                }
+                Err(Error::WriteBufferFull(_)) => {
+                    self.queue.lock().entries.push_front(entry);
+                    return ignore_would_block(socket.flush());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let close = {
+            let state = self.queue.lock();
+            if state.entries.is_empty() {
+                state.close.clone()
+            } else {
+                None
+            }
+        };
+        match close {
+            Some(frame) if socket.can_write() => ignore_would_block(socket.close(Some(frame))),
+            _ => ignore_would_block(socket.flush()),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut topics = self.shared.lock();
+        if let Some((_, subscribed)) = topics.subscribers.remove(&self.id) {
+            for topic in subscribed {
+                remove_from_topic(&mut topics.topics, &topic, self.id);
+            }
+        }
+        trace!("Subscriber {} removed", self.id);
+    }
+}
+
+fn take(state: &mut QueueState) -> Option<PreparedMessage> {
+    let entry = state.entries.pop_front()?;
+    state.delivered += 1;
+    Some(entry.message)
+}
+
+fn remove_from_topic(
+    topics: &mut HashMap<Arc<str>, HashMap<u64, Arc<Queue>>>,
+    topic: &str,
+    id: u64,
+) {
+    if let Some(subscribers) = topics.get_mut(topic) {
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            topics.remove(topic);
+        }
+    }
+}
+
+fn ignore_would_block(result: Result<()>) -> Result<()> {
+    match result {
+        Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hub, HubConfig, SlowConsumerPolicy};
+    use crate::protocol::{frame::coding::CloseCode, Message, Role, WebSocket};
+
+    use std::{io::Cursor, time::Duration};
+
+    fn hub(policy: SlowConsumerPolicy) -> Hub {
+        Hub::new(HubConfig { queue_capacity: 2, policy })
+    }
+
+    fn received(subscription: &super::Subscription) -> Vec<Message> {
+        std::iter::from_fn(|| subscription.try_recv()).map(|m| m.message().clone()).collect()
+    }
+
+    #[test]
+    fn topics() {
+        let hub = hub(SlowConsumerPolicy::DropNewest);
+        let prices = hub.subscribe();
+        prices.subscribe_to("prices");
+        let all = hub.subscribe();
+        all.subscribe_to("prices");
+        all.subscribe_to("news");
+
+        assert_eq!(hub.publish("prices", Message::text("1")), 2);
+        assert_eq!(hub.publish("news", Message::text("2")), 1);
+        assert_eq!(hub.publish("weather", Message::text("3")), 0);
+        assert_eq!(received(&prices), [Message::text("1")]);
+        assert_eq!(received(&all), [Message::text("1"), Message::text("2")]);
+
+        all.unsubscribe_from("prices");
+        drop(prices);
+        assert_eq!(hub.publish("prices", Message::text("4")), 0);
+        assert_eq!(hub.subscriber_count(), 1);
+        assert_eq!(hub.metrics()[0].delivered, 2);
+    }
+
+    #[test]
+    fn drop_policies() {
+        let hub = hub(SlowConsumerPolicy::DropOldest);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("t");
+        for i in 0..4 {
+            hub.publish("t", Message::text(i.to_string()));
+        }
+        let metrics = subscription.metrics();
+        assert_eq!((metrics.queued, metrics.dropped), (2, 2));
+        assert!(metrics.lag > Duration::ZERO);
+        assert_eq!(received(&subscription), [Message::text("2"), Message::text("3")]);
+
+        let hub = self::hub(SlowConsumerPolicy::DropNewest);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("t");
+        let queued: Vec<_> =
+            (0..4).map(|i| hub.publish("t", Message::text(i.to_string()))).collect();
+        assert_eq!(queued, [1, 1, 0, 0]);
+        assert_eq!(received(&subscription), [Message::text("0"), Message::text("1")]);
+        assert_eq!(subscription.metrics().delivered, 2);
+    }
+
+    #[test]
+    fn coalesce() {
+        let hub = hub(SlowConsumerPolicy::Coalesce);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("a");
+        subscription.subscribe_to("b");
+        hub.publish("a", Message::text("a1"));
+        hub.publish("b", Message::text("b1"));
+        hub.publish("a", Message::text("a2"));
+        hub.publish("b", Message::text("b2"));
+        assert_eq!(subscription.metrics().coalesced, 2);
+        assert_eq!(received(&subscription), [Message::text("a2"), Message::text("b2")]);
+
+        subscription.subscribe_to("c");
+        hub.publish("a", Message::text("a3"));
+        hub.publish("a", Message::text("a4"));
+        hub.publish("c", Message::text("c1"));
+        assert_eq!(subscription.metrics().dropped, 1);
+        assert_eq!(received(&subscription), [Message::text("a4"), Message::text("c1")]);
+    }
+
+    #[test]
+    fn disconnect() {
+        let hub = hub(SlowConsumerPolicy::Disconnect);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("t");
+        for i in 0..3 {
+            hub.publish("t", Message::text(i.to_string()));
+        }
+        assert!(subscription.is_disconnected());
+        assert_eq!(subscription.metrics().dropped, 3);
+        assert_eq!(hub.publish("t", Message::text("late")), 0);
+        assert!(subscription.recv().is_none());
+
+        let mut socket = WebSocket::from_raw_socket(Cursor::new(Vec::new()), Role::Server, None);
+        subscription.forward(&mut socket).unwrap();
+        let written = socket.get_ref().get_ref().clone();
+        let mut client = WebSocket::from_raw_socket(Cursor::new(written), Role::Client, None);
+        match client.read().unwrap() {
+            Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Policy),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn receive_with_timeout() {
+        let hub = hub(SlowConsumerPolicy::DropNewest);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("t");
+        assert!(subscription.recv_timeout(Duration::from_millis(10)).is_none());
+        hub.publish("t", Message::text("1"));
+        let message = subscription.recv_timeout(Duration::MAX).unwrap();
+        assert_eq!(*message.message(), Message::text("1"));
+        hub.close();
+        assert!(subscription.recv_timeout(Duration::MAX).is_none());
+    }
+
+    #[test]
+    fn close_hub() {
+        let hub = hub(SlowConsumerPolicy::DropNewest);
+        let subscription = hub.subscribe();
+        subscription.subscribe_to("t");
+        hub.publish("t", Message::text("last"));
+        hub.close();
+        assert!(hub.subscribe().is_disconnected());
+
+        let mut socket = WebSocket::from_raw_socket(Cursor::new(Vec::new()), Role::Server, None);
+        subscription.write_to(&mut socket).unwrap();
+        assert!(!socket.can_write());
+        subscription.write_to(&mut socket).unwrap();
+        let written = socket.get_ref().get_ref().clone();
+        let mut client = WebSocket::from_raw_socket(Cursor::new(written), Role::Client, None);
+        assert_eq!(client.read().unwrap(), Message::text("last"));
+        match client.read().unwrap() {
+            Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
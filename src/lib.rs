@@ -21,11 +21,20 @@ pub mod client;
 pub mod error;
 #[cfg(feature = "handshake")]
 pub mod handshake;
+pub mod hub;
+#[cfg(feature = "handshake")]
+pub mod listener;
 pub mod protocol;
 #[cfg(feature = "handshake")]
+pub mod proxy;
+#[cfg(all(feature = "mio", feature = "handshake"))]
+pub mod reactor;
+#[cfg(feature = "handshake")]
+pub mod reconnect;
+#[cfg(feature = "handshake")]
 mod server;
 pub mod stream;
-#[cfg(all(any(feature = "native-tls", feature = "__rustls-tls"), feature = "handshake"))]
+#[cfg(feature = "handshake")]
 mod tls;
 pub mod util;
 
@@ -41,8 +50,17 @@ pub use crate::{
 pub use crate::{
     client::{client, connect, ClientRequestBuilder},
     handshake::{client::ClientHandshake, server::ServerHandshake, HandshakeError},
-    server::{accept, accept_hdr, accept_hdr_with_config, accept_with_config},
+    server::{
+        accept, accept_hdr, accept_hdr_with_config, accept_hdr_with_handshake_config,
+        accept_with_config, accept_with_handshake_config,
+    },
+};
+
+#[cfg(feature = "handshake")]
+pub use tls::{
+    accept_tls, accept_tls_hdr_with_config, client_tls, client_tls_with_config,
+    client_tls_with_server_name, Acceptor, Connector, Identity, TlsConnect, TlsOptions,
 };
 
-#[cfg(all(any(feature = "native-tls", feature = "__rustls-tls"), feature = "handshake"))]
-pub use tls::{client_tls, client_tls_with_config, Connector};
+#[cfg(all(feature = "__rustls-tls", feature = "handshake"))]
+pub use tls::{SniCertificates, SniResolver, VerifyCallback};
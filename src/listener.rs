@@ -0,0 +1,482 @@
+//! A blocking server accepting WebSocket connections on a TCP listener.
+//!
+//! [`Server`] accepts connections, performs the handshake on a bounded pool of threads and hands
+//! every session to the handler routed by the request path and `Host` header. Each session
+//! occupies one thread of the pool until its handler returns, so the number of threads bounds the
+//! number of concurrent sessions, further connections wait in a queue.
+//!
+//! Connections over the global or per-IP limits of [`ServerConfig`] are rejected with
+//! `503 Service Unavailable` and `429 Too Many Requests` respectively, requests without a route
+//! with `404 Not Found`.
+//!
+//! [`ShutdownHandle::shutdown`] stops accepting connections and closes all sessions with
+//! [`CloseCode::Away`]. Blocked reads of the handlers are interrupted, the handlers should return
+//! once [`WebSocket::read`] fails.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Write,
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    },
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use http::{header::HOST, StatusCode};
+use log::*;
+
+use crate::{
+    error::Result,
+    handshake::{
+        server::{write_response, ErrorResponse, Request, Response},
+        HandshakeConfig,
+    },
+    protocol::{
+        frame::{coding::CloseCode, Frame},
+        CloseFrame, WebSocket, WebSocketConfig,
+    },
+    server::accept_hdr_with_handshake_config,
+};
+
+type Handler = dyn Fn(&mut WebSocket<TcpStream>, &Request) -> Result<()> + Send + Sync;
+
+/// The limits of a [`Server`] and the configuration of its sessions.
+#[derive(Debug, Clone, Copy)]
+pub struct ServerConfig {
+    /// The number of threads running sessions, i.e. the maximum number of concurrent sessions.
+    /// The default value is 64.
+    pub threads: usize,
+    /// The maximum number of connections, running sessions and connections waiting for a thread.
+    /// Further connections are rejected with `503 Service Unavailable`. The default value is 256.
+    pub max_connections: usize,
+    /// The maximum number of connections from one IP address. Further connections are rejected
+    /// with `429 Too Many Requests`. The default value is 16.
+    pub max_connections_per_ip: usize,
+    /// The configuration of the sessions, `None` uses the default configuration.
+    pub websocket: Option<WebSocketConfig>,
+    /// The limits enforced while reading the handshake request. If a timeout is set, it is also
+    /// used as the read and write timeout of the socket during the handshake. Without a timeout
+    /// an idle client occupies a thread of the pool indefinitely. The default value is the
+    /// default [`HandshakeConfig`] with a timeout of 10 seconds.
+    pub handshake: Option<HandshakeConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            threads: 64,
+            max_connections: 256,
+            max_connections_per_ip: 16,
+            websocket: None,
+            handshake: Some(HandshakeConfig {
+                timeout: Some(Duration::from_secs(10)),
+                ..HandshakeConfig::default()
+            }),
+        }
+    }
+}
+
+struct Route {
+    host: Option<String>,
+    path: String,
+    handler: Arc<Handler>,
+}
+
+impl Route {
+    fn matches(&self, request: &Request) -> bool {
+        if request.uri().path() != self.path {
+            return false;
+        }
+        match &self.host {
+            Some(host) => request_host(request).map_or(false, |h| h.eq_ignore_ascii_case(host)),
+            None => true,
+        }
+    }
+}
+
+/// The host of the `Host` header without the port.
+fn request_host(request: &Request) -> Option<&str> {
+    let host = request.headers().get(HOST)?.to_str().ok()?;
+    if host.starts_with('[') {
+        // An IPv6 address, keep the brackets.
+        return host.find(']').map(|end| &host[..=end]);
+    }
+    Some(host.rsplit_once(':').map_or(host, |(host, _)| host))
+}
+
+/// The connections of a server, shared by the accepting thread, the pool and the shutdown
+/// handles.
+#[derive(Default)]
+struct Connections {
+    shutting_down: bool,
+    per_ip: HashMap<IpAddr, usize>,
+    /// Clones of the sockets of all connections for waking blocked reads on shutdown. Without a
+    /// clone a connection can't be interrupted, but it is still served.
+    sockets: HashMap<u64, Option<TcpStream>>,
+    next_id: u64,
+}
+
+/// A connection counted against the limits until dropped.
+struct Registration {
+    id: u64,
+    ip: IpAddr,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock().unwrap();
+        connections.sockets.remove(&self.id);
+        if let Some(count) = connections.per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// A blocking WebSocket server routing sessions by path and host.
+///
+/// ```no_run
+/// use tungstenite::{
+///     listener::{Server, ServerConfig},
+///     Message,
+/// };
+///
+/// let server = Server::bind("127.0.0.1:9001", ServerConfig::default())
+///     .unwrap()
+///     .with_route("/echo", |socket, _request| loop {
+///         let msg = socket.read()?;
+///         if msg.is_text() || msg.is_binary() {
+///             socket.send(msg)?;
+///         }
+///     })
+///     .with_host_route("admin.example.com", "/status", |socket, _request| {
+///         socket.send(Message::text("ok"))
+///     });
+/// let shutdown = server.shutdown_handle();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+///     shutdown.shutdown();
+/// });
+/// server.run().unwrap();
+/// ```
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    routes: Vec<Route>,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<_> = self.routes.iter().map(|r| (&r.host, &r.path)).collect();
+        f.debug_struct("Server")
+            .field("listener", &self.listener)
+            .field("config", &self.config)
+            .field("routes", &routes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Server {
+    /// Create a server listening on the given address.
+    pub fn bind(addr: impl ToSocketAddrs, config: ServerConfig) -> Result<Self> {
+        Ok(Server::from_listener(TcpListener::bind(addr)?, config))
+    }
+
+    /// Create a server accepting connections of an existing listener.
+    pub fn from_listener(listener: TcpListener, config: ServerConfig) -> Self {
+        Server { listener, config, routes: Vec::new(), connections: Arc::default() }
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Route requests for `path` on any host to `handler`.
+    ///
+    /// Routes are matched in the order they were added, the first one matching the path and the
+    /// host of a request wins. The handler runs the session, which is closed after it returns.
+    /// Errors returned by the handler are logged.
+    pub fn with_route<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(&mut WebSocket<TcpStream>, &Request) -> Result<()> + Send + Sync + 'static,
+    {
+        self.add_route(None, path, Arc::new(handler))
+    }
+
+    /// Route requests for `path` with the `Host` header `host`, ignoring the port and the case, to
+    /// `handler`. See [`with_route`](Self::with_route).
+    pub fn with_host_route<F>(self, host: &str, path: &str, handler: F) -> Self
+    where
+        F: Fn(&mut WebSocket<TcpStream>, &Request) -> Result<()> + Send + Sync + 'static,
+    {
+        self.add_route(Some(host.to_owned()), path, Arc::new(handler))
+    }
+
+    fn add_route(mut self, host: Option<String>, path: &str, handler: Arc<Handler>) -> Self {
+        self.routes.push(Route { host, path: path.to_owned(), handler });
+        self
+    }
+
+    /// A handle to shut the server down from other threads.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            addr: self.listener.local_addr().ok(),
+            connections: self.connections.clone(),
+        }
+    }
+
+    /// Accept connections until the server is shut down, then wait for all sessions to end.
+    ///
+    /// Fails only if the thread pool can't be started. Errors accepting connections are logged.
+    pub fn run(self) -> Result<()> {
+        let Server { listener, config, routes, connections } = self;
+        let routes = Arc::new(routes);
+        let (sender, receiver) = mpsc::channel::<(TcpStream, Registration)>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut threads = Vec::with_capacity(config.threads);
+        for i in 0..config.threads.max(1) {
+            let receiver = receiver.clone();
+            let routes = routes.clone();
+            let shared = connections.clone();
+            let thread = thread::Builder::new().name(format!("websocket-{i}")).spawn(move || {
+                loop {
+                    // Release the lock before running the session.
+                    let next = receiver.lock().unwrap().recv();
+                    let (stream, registration) = match next {
+                        Ok(next) => next,
+                        Err(_) => break,
+                    };
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        serve(stream, &routes, &config, &shared);
+                    }));
+                    if result.is_err() {
+                        error!("WebSocket handler panicked");
+                    }
+                    drop(registration);
+                }
+            });
+            match thread {
+                Ok(thread) => threads.push(thread),
+                Err(err) => {
+                    ShutdownHandle { addr: listener.local_addr().ok(), connections }.shutdown();
+                    drop(sender);
+                    for thread in threads {
+                        let _ = thread.join();
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+
+        let mut backoff = MIN_ACCEPT_BACKOFF;
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => {
+                    backoff = MIN_ACCEPT_BACKOFF;
+                    stream
+                }
+                Err(err) => {
+                    if connections.lock().unwrap().shutting_down {
+                        break;
+                    }
+                    // E.g. out of file descriptors, which persists until connections end.
+                    warn!("Failed to accept a connection: {err}");
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                    continue;
+                }
+            };
+            let ip = match stream.peer_addr() {
+                Ok(addr) => addr.ip(),
+                Err(_) => continue,
+            };
+            match register(&connections, &config, &stream, ip) {
+                Ok(registration) => {
+                    sender.send((stream, registration)).expect("the pool outlives the listener")
+                }
+                Err(None) => break,
+                Err(Some(status)) => {
+                    debug!("Rejecting a connection from {ip}: {status}");
+                    reject(stream, status);
+                }
+            }
+        }
+
+        drop(sender);
+        for thread in threads {
+            let _ = thread.join();
+        }
+        Ok(())
+    }
+}
+
+/// The initial delay before accepting again after accepting a connection failed.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+/// The maximum delay before accepting again while accepting connections keeps failing.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Count a new connection against the limits. Fails with `None` when shutting down, or the
+/// status to reject the connection with.
+fn register(
+    connections: &Arc<Mutex<Connections>>,
+    config: &ServerConfig,
+    stream: &TcpStream,
+    ip: IpAddr,
+) -> std::result::Result<Registration, Option<StatusCode>> {
+    let mut guard = connections.lock().unwrap();
+    if guard.shutting_down {
+        return Err(None);
+    }
+    if guard.sockets.len() >= config.max_connections {
+        return Err(Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+    let count = guard.per_ip.entry(ip).or_insert(0);
+    if *count >= config.max_connections_per_ip {
+        return Err(Some(StatusCode::TOO_MANY_REQUESTS));
+    }
+    *count += 1;
+    let id = guard.next_id;
+    guard.next_id += 1;
+    guard.sockets.insert(id, stream.try_clone().ok());
+    Ok(Registration { id, ip, connections: connections.clone() })
+}
+
+fn reject(mut stream: TcpStream, status: StatusCode) {
+    let response = http::Response::builder()
+        .status(status)
+        .header("Connection", "close")
+        .header("Content-Length", "0")
+        .body(())
+        .expect("a valid response");
+    let mut output = Vec::new();
+    if write_response(&mut output, &response).is_ok() {
+        let _ = stream.write_all(&output);
+    }
+    let _ = stream.shutdown(Shutdown::Write);
+}
+
+/// Perform the handshake and run the routed handler.
+fn serve(
+    stream: TcpStream,
+    routes: &[Route],
+    config: &ServerConfig,
+    connections: &Mutex<Connections>,
+) {
+    let timeout = config.handshake.and_then(|c| c.timeout);
+    if timeout.is_some() {
+        let _ = stream.set_read_timeout(timeout);
+        let _ = stream.set_write_timeout(timeout);
+    }
+
+    let mut routed = None;
+    let callback = |request: &Request, response: Response| match routes
+        .iter()
+        .find(|route| route.matches(request))
+    {
+        Some(route) => {
+            routed = Some((route.handler.clone(), request.clone()));
+            Ok(response)
+        }
+        None => {
+            let mut response = ErrorResponse::new(None);
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            Err(response)
+        }
+    };
+    let mut socket = match accept_hdr_with_handshake_config(
+        stream,
+        callback,
+        config.websocket,
+        config.handshake,
+    ) {
+        Ok(socket) => socket,
+        Err(err) => {
+            debug!("WebSocket handshake failed: {err}");
+            return;
+        }
+    };
+    let (handler, request) = routed.expect("the handshake succeeds only for routed requests");
+    if timeout.is_some() {
+        let _ = socket.get_mut().set_read_timeout(None);
+        let _ = socket.get_mut().set_write_timeout(None);
+    }
+
+    if let Err(err) = handler(&mut socket, &request) {
+        debug!("WebSocket handler for {} failed: {err}", request.uri().path());
+    }
+
+    let shutting_down = connections.lock().unwrap().shutting_down;
+    let frame = if shutting_down {
+        CloseFrame { code: CloseCode::Away, reason: "server shutting down".into() }
+    } else {
+        CloseFrame { code: CloseCode::Normal, reason: "".into() }
+    };
+    if socket.can_write() {
+        let _ = socket.close(Some(frame));
+        let _ = socket.flush();
+    } else if shutting_down {
+        // The read interrupted by the shutdown terminated the session without closing it.
+        let mut output = Vec::new();
+        if Frame::close(Some(frame)).format(&mut output).is_ok() {
+            let _ = socket.get_mut().write_all(&output);
+        }
+    }
+    let _ = socket.get_mut().shutdown(Shutdown::Both);
+}
+
+/// Shuts a [`Server`] down, see [`Server::shutdown_handle`].
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    addr: Option<SocketAddr>,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownHandle").field("addr", &self.addr).finish_non_exhaustive()
+    }
+}
+
+impl ShutdownHandle {
+    /// Stop accepting connections and close all sessions with [`CloseCode::Away`].
+    ///
+    /// Blocked reads of the handlers fail, and the session is closed once the handler returned.
+    /// [`Server::run`] returns after all sessions ended.
+    pub fn shutdown(&self) {
+        let mut connections = self.connections.lock().unwrap();
+        if connections.shutting_down {
+            return;
+        }
+        connections.shutting_down = true;
+        for socket in connections.sockets.values().flatten() {
+            let _ = socket.shutdown(Shutdown::Read);
+        }
+        drop(connections);
+
+        // Wake the accepting thread.
+        if let Some(mut addr) = self.addr {
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr {
+                    SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                });
+            }
+            let _ = TcpStream::connect(addr);
+        }
+    }
+
+    /// Whether [`shutdown`](Self::shutdown) was called.
+    pub fn is_shut_down(&self) -> bool {
+        self.connections.lock().unwrap().shutting_down
+    }
+}
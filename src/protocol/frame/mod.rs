@@ -8,6 +8,7 @@ mod mask;
 
 use crate::{
     error::{CapacityError, Error, Result},
+    protocol::PreparedMessage,
     Message, ReadBuffer,
 };
 use log::*;
@@ -228,6 +229,48 @@ impl FrameCodec {
         }
     }
 
+    /// Writes encoded frames into the `out_buffer`, like [`Self::buffer_frame`].
+    pub(in crate::protocol) fn buffer_prepared<Stream>(
+        &mut self,
+        stream: &mut Stream,
+        message: &PreparedMessage,
+    ) -> Result<()>
+    where
+        Stream: Write,
+    {
+        let bytes = message.as_bytes();
+        if bytes.len() + self.out_buffer.len() > self.max_out_buffer_len {
+            return Err(Error::WriteBufferFull(message.message().clone()));
+        }
+
+        trace!("writing prepared message {}", message.message());
+
+        self.out_buffer.extend_from_slice(bytes);
+
+        if self.out_buffer.len() > self.out_buffer_write_len {
+            self.write_out_buffer(stream)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Moves the out_buffer and its limits into a new codec writing to a separate handle of the
+    /// stream.
+    pub(super) fn split_off_writes(&mut self) -> FrameCodec {
+        FrameCodec {
+            in_buffer: ReadBuffer::new(),
+            out_buffer: std::mem::take(&mut self.out_buffer),
+            max_out_buffer_len: self.max_out_buffer_len,
+            out_buffer_write_len: self.out_buffer_write_len,
+            header: None,
+        }
+    }
+
+    /// Whether encoded frames are waiting in the out_buffer.
+    pub(super) fn has_buffered_writes(&self) -> bool {
+        !self.out_buffer.is_empty()
+    }
+
     /// Writes the out_buffer to the provided stream.
     ///
     /// Does **not** flush.
@@ -3,8 +3,15 @@
 pub mod frame;
 
 mod message;
-
-pub use self::{frame::CloseFrame, message::Message};
+mod prepared;
+mod split;
+
+pub use self::{
+    frame::CloseFrame,
+    message::Message,
+    prepared::PreparedMessage,
+    split::{WebSocketReader, WebSocketWriter},
+};
 
 use self::{
     frame::{
@@ -180,6 +187,20 @@ impl<Stream> WebSocket<Stream> {
     pub fn can_write(&self) -> bool {
         self.context.can_write()
     }
+
+    /// Check if the connection should be polled for read readiness.
+    ///
+    /// This is the case until the connection is terminated, see
+    /// [`WebSocketContext::wants_read`].
+    pub fn wants_read(&self) -> bool {
+        self.context.wants_read()
+    }
+
+    /// Check if the connection should be polled for write readiness, i.e. there is buffered
+    /// data [`flush`](Self::flush) has yet to write, see [`WebSocketContext::wants_write`].
+    pub fn wants_write(&self) -> bool {
+        self.context.wants_write()
+    }
 }
 
 impl<Stream: Read + Write> WebSocket<Stream> {
@@ -245,6 +266,22 @@ impl<Stream: Read + Write> WebSocket<Stream> {
         self.context.write(&mut self.socket, message)
     }
 
+    /// Write a message encoded in advance, see [`write`](Self::write).
+    ///
+    /// Only servers can write prepared messages, clients fail with
+    /// [`ProtocolError::PreparedMessageFromClient`]. Like [`close`](Self::close), a prepared
+    /// close message starts the close handshake.
+    pub fn write_prepared(&mut self, message: &PreparedMessage) -> Result<()> {
+        self.context.write_prepared(&mut self.socket, message)
+    }
+
+    /// Writes and immediately flushes a message encoded in advance.
+    /// Equivalent to calling [`write_prepared`](Self::write_prepared) then [`flush`](Self::flush).
+    pub fn send_prepared(&mut self, message: &PreparedMessage) -> Result<()> {
+        self.write_prepared(message)?;
+        self.flush()
+    }
+
     /// Flush writes.
     ///
     /// Ensures all messages previously passed to [`write`](Self::write) and automatic
@@ -380,6 +417,26 @@ impl WebSocketContext {
         self.state.is_active()
     }
 
+    /// Check if the stream should be polled for read readiness.
+    ///
+    /// Reading is needed until the connection is terminated, also after a close frame was
+    /// exchanged to receive the reply or the end of the stream.
+    pub fn wants_read(&self) -> bool {
+        self.state != WebSocketState::Terminated
+    }
+
+    /// Check if the stream should be polled for write readiness.
+    ///
+    /// This is the case if written messages, a partially written frame or an automatic pong or
+    /// close reply are waiting to be written to the stream by [`flush`](Self::flush). Data
+    /// buffered by the stream itself, e.g. by a TLS stream, is not taken into account.
+    pub fn wants_write(&self) -> bool {
+        self.state != WebSocketState::Terminated
+            && (self.additional_send.is_some()
+                || self.unflushed_additional
+                || self.frame.has_buffered_writes())
+    }
+
     /// Read a message from the provided stream, if possible.
     ///
     /// This function sends pong and close responses automatically.
@@ -458,6 +515,48 @@ impl WebSocketContext {
         Ok(())
     }
 
+    /// Write a message encoded in advance to the provided stream, see [`write`](Self::write).
+    ///
+    /// Only servers can write prepared messages, clients fail with
+    /// [`ProtocolError::PreparedMessageFromClient`].
+    pub fn write_prepared<Stream>(
+        &mut self,
+        stream: &mut Stream,
+        message: &PreparedMessage,
+    ) -> Result<()>
+    where
+        Stream: Read + Write,
+    {
+        // Clients mask every frame with a new key.
+        if self.role == Role::Client {
+            return Err(Error::Protocol(ProtocolError::PreparedMessageFromClient));
+        }
+
+        // When terminated, return AlreadyClosed.
+        self.state.check_not_terminated()?;
+
+        if message.is_close() {
+            if let WebSocketState::Active = self.state {
+                self.state = WebSocketState::ClosedByUs;
+                self.buffer_prepared(stream, message)?;
+                self._write(stream, None)?;
+            }
+            return self.flush(stream);
+        }
+
+        // Do not write after sending a close frame.
+        if !self.state.is_active() {
+            return Err(Error::Protocol(ProtocolError::SendAfterClosing));
+        }
+
+        self.buffer_prepared(stream, message)?;
+        let should_flush = self._write(stream, None)?;
+        if should_flush {
+            self.flush(stream)?;
+        }
+        Ok(())
+    }
+
     /// Flush writes.
     ///
     /// Ensures all messages previously passed to [`write`](Self::write) and automatically
@@ -545,128 +644,132 @@ impl WebSocketContext {
     where
         Stream: Read + Write,
     {
-        if let Some(mut frame) = self
-            .frame
-            .read_frame(stream, self.config.max_frame_size)
-            .check_connection_reset(self.state)?
-        {
-            if !self.state.can_read() {
-                return Err(Error::Protocol(ProtocolError::ReceivedAfterClosing));
-            }
-            // MUST be 0 unless an extension is negotiated that defines meanings
-            // for non-zero values.  If a nonzero value is received and none of
-            // the negotiated extensions defines the meaning of such a nonzero
-            // value, the receiving endpoint MUST _Fail the WebSocket
-            // Connection_.
-            {
-                let hdr = frame.header();
-                if hdr.rsv1 || hdr.rsv2 || hdr.rsv3 {
-                    return Err(Error::Protocol(ProtocolError::NonZeroReservedBits));
+        if let Some(frame) = self.read_frame(stream)? {
+            self.process_frame(frame)
+        } else {
+            // Connection closed by peer
+            match replace(&mut self.state, WebSocketState::Terminated) {
+                WebSocketState::ClosedByPeer | WebSocketState::CloseAcknowledged => {
+                    Err(Error::ConnectionClosed)
                 }
+                _ => Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)),
             }
+        }
+    }
 
-            match self.role {
-                Role::Server => {
-                    if frame.is_masked() {
-                        // A server MUST remove masking for data frames received from a client
-                        // as described in Section 5.3. (RFC 6455)
-                        frame.apply_mask()
-                    } else if !self.config.accept_unmasked_frames {
-                        // The server MUST close the connection upon receiving a
-                        // frame that is not masked. (RFC 6455)
-                        // The only exception here is if the user explicitly accepts given
-                        // stream by setting WebSocketConfig.accept_unmasked_frames to true
-                        return Err(Error::Protocol(ProtocolError::UnmaskedFrameFromClient));
-                    }
+    /// Read a frame from the stream, `None` if the peer closed the connection.
+    fn read_frame<Stream: Read>(&mut self, stream: &mut Stream) -> Result<Option<Frame>> {
+        self.frame.read_frame(stream, self.config.max_frame_size).check_connection_reset(self.state)
+    }
+
+    /// Handle a received frame. Replies to control frames are queued in `additional_send`.
+    fn process_frame(&mut self, mut frame: Frame) -> Result<Option<Message>> {
+        if !self.state.can_read() {
+            return Err(Error::Protocol(ProtocolError::ReceivedAfterClosing));
+        }
+        // MUST be 0 unless an extension is negotiated that defines meanings
+        // for non-zero values.  If a nonzero value is received and none of
+        // the negotiated extensions defines the meaning of such a nonzero
+        // value, the receiving endpoint MUST _Fail the WebSocket
+        // Connection_.
+        {
+            let hdr = frame.header();
+            if hdr.rsv1 || hdr.rsv2 || hdr.rsv3 {
+                return Err(Error::Protocol(ProtocolError::NonZeroReservedBits));
+            }
+        }
+
+        match self.role {
+            Role::Server => {
+                if frame.is_masked() {
+                    // A server MUST remove masking for data frames received from a client
+                    // as described in Section 5.3. (RFC 6455)
+                    frame.apply_mask()
+                } else if !self.config.accept_unmasked_frames {
+                    // The server MUST close the connection upon receiving a
+                    // frame that is not masked. (RFC 6455)
+                    // The only exception here is if the user explicitly accepts given
+                    // stream by setting WebSocketConfig.accept_unmasked_frames to true
+                    return Err(Error::Protocol(ProtocolError::UnmaskedFrameFromClient));
                 }
-                Role::Client => {
-                    if frame.is_masked() {
-                        // A client MUST close a connection if it detects a masked frame. (RFC 6455)
-                        return Err(Error::Protocol(ProtocolError::MaskedFrameFromServer));
-                    }
+            }
+            Role::Client => {
+                if frame.is_masked() {
+                    // A client MUST close a connection if it detects a masked frame. (RFC 6455)
+                    return Err(Error::Protocol(ProtocolError::MaskedFrameFromServer));
                 }
             }
+        }
 
-            match frame.header().opcode {
-                OpCode::Control(ctl) => {
-                    match ctl {
-                        // All control frames MUST have a payload length of 125 bytes or less
-                        // and MUST NOT be fragmented. (RFC 6455)
-                        _ if !frame.header().is_final => {
-                            Err(Error::Protocol(ProtocolError::FragmentedControlFrame))
-                        }
-                        _ if frame.payload().len() > 125 => {
-                            Err(Error::Protocol(ProtocolError::ControlFrameTooBig))
-                        }
-                        OpCtl::Close => Ok(self.do_close(frame.into_close()?).map(Message::Close)),
-                        OpCtl::Reserved(i) => {
-                            Err(Error::Protocol(ProtocolError::UnknownControlFrameType(i)))
-                        }
-                        OpCtl::Ping => {
-                            let data = frame.into_data();
-                            // No ping processing after we sent a close frame.
-                            if self.state.is_active() {
-                                self.set_additional(Frame::pong(data.clone()));
-                            }
-                            Ok(Some(Message::Ping(data)))
+        match frame.header().opcode {
+            OpCode::Control(ctl) => {
+                match ctl {
+                    // All control frames MUST have a payload length of 125 bytes or less
+                    // and MUST NOT be fragmented. (RFC 6455)
+                    _ if !frame.header().is_final => {
+                        Err(Error::Protocol(ProtocolError::FragmentedControlFrame))
+                    }
+                    _ if frame.payload().len() > 125 => {
+                        Err(Error::Protocol(ProtocolError::ControlFrameTooBig))
+                    }
+                    OpCtl::Close => Ok(self.do_close(frame.into_close()?).map(Message::Close)),
+                    OpCtl::Reserved(i) => {
+                        Err(Error::Protocol(ProtocolError::UnknownControlFrameType(i)))
+                    }
+                    OpCtl::Ping => {
+                        let data = frame.into_data();
+                        // No ping processing after we sent a close frame.
+                        if self.state.is_active() {
+                            self.set_additional(Frame::pong(data.clone()));
                         }
-                        OpCtl::Pong => Ok(Some(Message::Pong(frame.into_data()))),
+                        Ok(Some(Message::Ping(data)))
                     }
+                    OpCtl::Pong => Ok(Some(Message::Pong(frame.into_data()))),
                 }
+            }
 
-                OpCode::Data(data) => {
-                    let fin = frame.header().is_final;
-                    match data {
-                        OpData::Continue => {
-                            if let Some(ref mut msg) = self.incomplete {
-                                msg.extend(frame.into_data(), self.config.max_message_size)?;
-                            } else {
-                                return Err(Error::Protocol(
-                                    ProtocolError::UnexpectedContinueFrame,
-                                ));
-                            }
-                            if fin {
-                                Ok(Some(self.incomplete.take().unwrap().complete()?))
-                            } else {
-                                Ok(None)
-                            }
+            OpCode::Data(data) => {
+                let fin = frame.header().is_final;
+                match data {
+                    OpData::Continue => {
+                        if let Some(ref mut msg) = self.incomplete {
+                            msg.extend(frame.into_data(), self.config.max_message_size)?;
+                        } else {
+                            return Err(Error::Protocol(ProtocolError::UnexpectedContinueFrame));
                         }
-                        c if self.incomplete.is_some() => {
-                            Err(Error::Protocol(ProtocolError::ExpectedFragment(c)))
+                        if fin {
+                            Ok(Some(self.incomplete.take().unwrap().complete()?))
+                        } else {
+                            Ok(None)
                         }
-                        OpData::Text | OpData::Binary => {
-                            let msg = {
-                                let message_type = match data {
-                                    OpData::Text => IncompleteMessageType::Text,
-                                    OpData::Binary => IncompleteMessageType::Binary,
-                                    _ => panic!("Bug: message is not text nor binary"),
-                                };
-                                let mut m = IncompleteMessage::new(message_type);
-                                m.extend(frame.into_data(), self.config.max_message_size)?;
-                                m
+                    }
+                    c if self.incomplete.is_some() => {
+                        Err(Error::Protocol(ProtocolError::ExpectedFragment(c)))
+                    }
+                    OpData::Text | OpData::Binary => {
+                        let msg = {
+                            let message_type = match data {
+                                OpData::Text => IncompleteMessageType::Text,
+                                OpData::Binary => IncompleteMessageType::Binary,
+                                _ => panic!("Bug: message is not text nor binary"),
                             };
-                            if fin {
-                                Ok(Some(msg.complete()?))
-                            } else {
-                                self.incomplete = Some(msg);
-                                Ok(None)
-                            }
-                        }
-                        OpData::Reserved(i) => {
-                            Err(Error::Protocol(ProtocolError::UnknownDataFrameType(i)))
+                            let mut m = IncompleteMessage::new(message_type);
+                            m.extend(frame.into_data(), self.config.max_message_size)?;
+                            m
+                        };
+                        if fin {
+                            Ok(Some(msg.complete()?))
+                        } else {
+                            self.incomplete = Some(msg);
+                            Ok(None)
                         }
                     }
+                    OpData::Reserved(i) => {
+                        Err(Error::Protocol(ProtocolError::UnknownDataFrameType(i)))
+                    }
                 }
-            } // match opcode
-        } else {
-            // Connection closed by peer
-            match replace(&mut self.state, WebSocketState::Terminated) {
-                WebSocketState::ClosedByPeer | WebSocketState::CloseAcknowledged => {
-                    Err(Error::ConnectionClosed)
-                }
-                _ => Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)),
             }
-        }
+        } // match opcode
     }
 
     /// Received a close frame. Tells if we need to return a close frame to the user.
@@ -725,6 +828,19 @@ impl WebSocketContext {
         self.frame.buffer_frame(stream, frame).check_connection_reset(self.state)
     }
 
+    /// Write a prepared message into the write-buffer.
+    fn buffer_prepared<Stream>(
+        &mut self,
+        stream: &mut Stream,
+        message: &PreparedMessage,
+    ) -> Result<()>
+    where
+        Stream: Read + Write,
+    {
+        trace!("Sending prepared message: {:?}", message);
+        self.frame.buffer_prepared(stream, message).check_connection_reset(self.state)
+    }
+
     /// Replace `additional_send` if it is currently a `Pong` message.
     fn set_additional(&mut self, add: Frame) {
         let empty_or_pong = self
@@ -796,8 +912,11 @@ impl<T> CheckConnectionReset for Result<T> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Message, Role, WebSocket, WebSocketConfig};
-    use crate::error::{CapacityError, Error};
+    use super::{
+        frame::coding::CloseCode, CloseFrame, Message, PreparedMessage, Role, WebSocket,
+        WebSocketConfig,
+    };
+    use crate::error::{CapacityError, Error, ProtocolError};
 
     use std::{io, io::Cursor};
 
@@ -858,4 +977,126 @@ mod tests {
             Err(Error::Capacity(CapacityError::MessageTooLong { size: 3, max_size: 2 }))
         ));
     }
+
+    #[test]
+    fn write_prepared() {
+        let messages = [
+            PreparedMessage::new(Message::text("Hello")),
+            PreparedMessage::fragmented(Message::binary(vec![1, 2, 3, 4, 5]), 2),
+            PreparedMessage::new(Message::Ping(vec![1])),
+        ];
+        let mut server = WebSocket::from_raw_socket(Cursor::new(Vec::new()), Role::Server, None);
+        for message in &messages {
+            server.write_prepared(message).unwrap();
+        }
+        server.flush().unwrap();
+        let written = server.get_ref().get_ref().clone();
+
+        let mut client =
+            WebSocket::from_raw_socket(WriteMoc(Cursor::new(written)), Role::Client, None);
+        for message in &messages {
+            assert_eq!(&client.read().unwrap(), message.message());
+        }
+    }
+
+    #[test]
+    fn write_prepared_close() {
+        let close = CloseFrame { code: CloseCode::Away, reason: "bye".into() };
+        let prepared = PreparedMessage::new(Message::Close(Some(close.clone())));
+        let mut server = WebSocket::from_raw_socket(Cursor::new(Vec::new()), Role::Server, None);
+        server.write_prepared(&prepared).unwrap();
+        assert!(!server.can_write());
+        assert!(matches!(
+            server.write_prepared(&PreparedMessage::new(Message::text("late"))),
+            Err(Error::Protocol(ProtocolError::SendAfterClosing))
+        ));
+
+        let written = server.get_ref().get_ref().clone();
+        let mut client =
+            WebSocket::from_raw_socket(WriteMoc(Cursor::new(written)), Role::Client, None);
+        assert_eq!(client.read().unwrap(), Message::Close(Some(close)));
+    }
+
+    #[test]
+    fn write_prepared_errors() {
+        let prepared = PreparedMessage::new(Message::binary(vec![0; 16]));
+        let mut client = WebSocket::from_raw_socket(Cursor::new(Vec::new()), Role::Client, None);
+        assert!(matches!(
+            client.write_prepared(&prepared),
+            Err(Error::Protocol(ProtocolError::PreparedMessageFromClient))
+        ));
+
+        let config = WebSocketConfig {
+            write_buffer_size: 8,
+            max_write_buffer_size: 16,
+            ..WebSocketConfig::default()
+        };
+        let mut server = WebSocket::from_raw_socket(
+            WriteMoc(Cursor::new(Vec::new())),
+            Role::Server,
+            Some(config),
+        );
+        match server.write_prepared(&prepared) {
+            Err(Error::WriteBufferFull(message)) => assert_eq!(&message, prepared.message()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    /// Reads from a cursor, writes fail with `WouldBlock` while blocked.
+    struct BlockingWrites {
+        incoming: Cursor<Vec<u8>>,
+        blocked: bool,
+    }
+
+    impl io::Read for BlockingWrites {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.incoming.read(buf)? {
+                0 => Err(io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl io::Write for BlockingWrites {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.blocked {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn readiness_interest() {
+        // A ping, then a close frame, both masked with a zero key.
+        let incoming = vec![0x89, 0x81, 0, 0, 0, 0, 0x01, 0x88, 0x80, 0, 0, 0, 0];
+        let stream = BlockingWrites { incoming: Cursor::new(incoming), blocked: true };
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        assert!(socket.wants_read());
+        assert!(!socket.wants_write());
+
+        // The pong can't be written.
+        assert_eq!(socket.read().unwrap(), Message::Ping(vec![1]));
+        assert!(socket.wants_write());
+        socket.get_mut().blocked = false;
+        socket.flush().unwrap();
+        assert!(!socket.wants_write());
+
+        socket.get_mut().blocked = true;
+        socket.write(Message::text("buffered")).unwrap();
+        assert!(socket.wants_write());
+        assert!(matches!(socket.flush(), Err(Error::Io(_))));
+        assert!(socket.wants_write());
+
+        // The close reply is queued behind the buffered message.
+        assert_eq!(socket.read().unwrap(), Message::Close(None));
+        assert!(socket.wants_read() && socket.wants_write());
+        socket.get_mut().blocked = false;
+        assert!(matches!(socket.flush(), Err(Error::ConnectionClosed)));
+        assert!(!socket.wants_read());
+        assert!(!socket.wants_write());
+    }
 }
@@ -0,0 +1,151 @@
+use std::{fmt, sync::Arc};
+
+use super::{
+    frame::{
+        coding::{Data as OpData, OpCode},
+        Frame, FrameHeader,
+    },
+    Message,
+};
+
+/// A message encoded once into the bytes sent over the wire, for sending it to many connections.
+///
+/// Messages sent by servers are not masked, so the encoded frames are the same for every
+/// connection and [`WebSocket::write_prepared`](super::WebSocket::write_prepared) copies them
+/// into the write buffer as they are. Clients mask every frame with a random key and can't send
+/// prepared messages.
+///
+/// Cloning is cheap, the encoded message is shared.
+///
+/// ```no_run
+/// use std::net::TcpStream;
+/// use tungstenite::{protocol::PreparedMessage, Message, WebSocket};
+///
+/// fn broadcast(sockets: &mut [WebSocket<TcpStream>], text: &str) {
+///     let message = PreparedMessage::new(Message::text(text));
+///     for socket in sockets {
+///         if let Err(err) = socket.send_prepared(&message) {
+///             eprintln!("Failed to send: {err}");
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PreparedMessage {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    message: Message,
+    bytes: Vec<u8>,
+}
+
+impl PreparedMessage {
+    /// Encode a message into a single frame.
+    pub fn new(message: Message) -> Self {
+        Self::fragmented(message, usize::MAX)
+    }
+
+    /// Encode a message, splitting text and binary messages into frames with payloads of at most
+    /// `fragment_size` bytes. Control frames are never fragmented.
+    ///
+    /// Text messages may be split within a UTF-8 sequence, receivers validate the whole message.
+    ///
+    /// # Panics
+    /// Panics if `fragment_size` is 0.
+    pub fn fragmented(message: Message, fragment_size: usize) -> Self {
+        assert!(fragment_size > 0, "fragment_size must be greater than 0");
+        let mut bytes = Vec::new();
+        let (opcode, payload) = match &message {
+            Message::Text(text) => (OpData::Text, text.as_bytes()),
+            Message::Binary(data) => (OpData::Binary, &data[..]),
+            Message::Ping(data) => {
+                return Self::from_frame(message.clone(), Frame::ping(data.clone()))
+            }
+            Message::Pong(data) => {
+                return Self::from_frame(message.clone(), Frame::pong(data.clone()))
+            }
+            Message::Close(frame) => {
+                let frame = Frame::close(frame.clone());
+                return Self::from_frame(message, frame);
+            }
+            Message::Frame(frame) => return Self::from_frame(message.clone(), frame.clone()),
+        };
+
+        let mut chunks = payload.chunks(fragment_size);
+        let mut opcode = OpCode::Data(opcode);
+        // An empty message is sent as one empty frame.
+        let mut next = Some(chunks.next().unwrap_or_default());
+        while let Some(chunk) = next {
+            next = chunks.next();
+            let header = FrameHeader { is_final: next.is_none(), opcode, ..FrameHeader::default() };
+            header.format(chunk.len() as u64, &mut bytes).expect("Bug: can't write to vector");
+            bytes.extend_from_slice(chunk);
+            opcode = OpCode::Data(OpData::Continue);
+        }
+        PreparedMessage { inner: Arc::new(Inner { message, bytes }) }
+    }
+
+    fn from_frame(message: Message, frame: Frame) -> Self {
+        let mut bytes = Vec::with_capacity(frame.len());
+        frame.format(&mut bytes).expect("Bug: can't write to vector");
+        PreparedMessage { inner: Arc::new(Inner { message, bytes }) }
+    }
+
+    /// The message that was encoded.
+    pub fn message(&self) -> &Message {
+        &self.inner.message
+    }
+
+    /// The encoded frames.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.inner.bytes
+    }
+
+    /// Whether the message is a close message, which starts the close handshake when written.
+    pub(super) fn is_close(&self) -> bool {
+        self.inner.message.is_close()
+    }
+}
+
+impl fmt::Debug for PreparedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreparedMessage")
+            .field("message", &self.inner.message)
+            .field("len", &self.inner.bytes.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreparedMessage;
+    use crate::protocol::{frame::coding::CloseCode, CloseFrame, Message};
+
+    #[test]
+    fn single_frame() {
+        let prepared = PreparedMessage::new(Message::text("Hello"));
+        assert_eq!(prepared.as_bytes(), b"\x81\x05Hello");
+        let prepared = PreparedMessage::new(Message::binary(vec![0; 300]));
+        assert_eq!(&prepared.as_bytes()[..4], [0x82, 0x7e, 0x01, 0x2c]);
+        assert_eq!(prepared.as_bytes().len(), 304);
+        let prepared = PreparedMessage::new(Message::Ping(vec![1, 2]));
+        assert_eq!(prepared.as_bytes(), [0x89, 0x02, 0x01, 0x02]);
+        let close = CloseFrame { code: CloseCode::Away, reason: "bye".into() };
+        let prepared = PreparedMessage::new(Message::Close(Some(close)));
+        assert_eq!(prepared.as_bytes(), b"\x88\x05\x03\xe9bye");
+        assert!(prepared.is_close());
+    }
+
+    #[test]
+    fn fragments() {
+        let prepared = PreparedMessage::fragmented(Message::text("Hello, World!"), 7);
+        assert_eq!(prepared.as_bytes(), b"\x01\x07Hello, \x80\x06World!");
+        let prepared = PreparedMessage::fragmented(Message::binary(vec![1, 2, 3, 4]), 2);
+        assert_eq!(prepared.as_bytes(), [0x02, 0x02, 0x01, 0x02, 0x80, 0x02, 0x03, 0x04]);
+        let prepared = PreparedMessage::fragmented(Message::binary(Vec::new()), 2);
+        assert_eq!(prepared.as_bytes(), [0x82, 0x00]);
+        let prepared = PreparedMessage::fragmented(Message::Ping(vec![1, 2, 3]), 1);
+        assert_eq!(prepared.as_bytes(), [0x89, 0x03, 0x01, 0x02, 0x03]);
+    }
+}
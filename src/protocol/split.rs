@@ -0,0 +1,315 @@
+use std::{
+    io::{self, Read, Write},
+    mem::replace,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{
+    frame::{
+        coding::{Control as OpCtl, OpCode},
+        Frame,
+    },
+    CloseFrame, Message, PreparedMessage, Role, WebSocket, WebSocketConfig, WebSocketContext,
+    WebSocketState,
+};
+use crate::{
+    error::{Error, ProtocolError, Result},
+    stream::TryClone,
+};
+
+impl<Stream: Read + Write + TryClone> WebSocket<Stream> {
+    /// Split the WebSocket into a reader and a writer half using two handles of the stream, so
+    /// one thread can block in [`WebSocketReader::read`] while others write.
+    ///
+    /// Pongs and close replies are sent through the writer, the close state is shared by both
+    /// halves. Messages already buffered for writing are kept by the writer. The stream is
+    /// closed once both halves are dropped.
+    ///
+    /// Fails if the stream can't be cloned, e.g. a TLS stream.
+    ///
+    /// ```no_run
+    /// use std::thread::spawn;
+    /// use tungstenite::{connect, Message};
+    ///
+    /// let (socket, _) = connect("ws://localhost:3012/socket").unwrap();
+    /// let (mut reader, mut writer) = socket.split().unwrap();
+    /// spawn(move || {
+    ///     while let Ok(msg) = reader.read() {
+    ///         println!("Received: {msg}");
+    ///     }
+    /// });
+    /// writer.send(Message::text("Hello")).unwrap();
+    /// ```
+    pub fn split(self) -> Result<(WebSocketReader<Stream>, WebSocketWriter<Stream>)> {
+        let WebSocket { socket, mut context } = self;
+        let writer = WebSocketContext {
+            role: context.role,
+            frame: context.frame.split_off_writes(),
+            state: context.state,
+            incomplete: None,
+            additional_send: context.additional_send.take(),
+            unflushed_additional: replace(&mut context.unflushed_additional, false),
+            config: context.config,
+        };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(context.state),
+            writer: Mutex::new(WriteHalf { stream: socket.try_clone()?, context: writer }),
+        });
+        let reader = WebSocketReader {
+            stream: socket,
+            context,
+            shared: shared.clone(),
+            reported_closed: false,
+        };
+        Ok((reader, WebSocketWriter { shared }))
+    }
+}
+
+/// The state shared by the halves of a split WebSocket.
+#[derive(Debug)]
+struct Shared<Stream> {
+    /// The close state of the connection. It is locked only to update it, never during I/O.
+    state: Mutex<WebSocketState>,
+    writer: Mutex<WriteHalf<Stream>>,
+}
+
+#[derive(Debug)]
+struct WriteHalf<Stream> {
+    stream: Stream,
+    context: WebSocketContext,
+}
+
+impl<Stream> Shared<Stream> {
+    fn state(&self) -> WebSocketState {
+        *self.state.lock().unwrap()
+    }
+}
+
+impl<Stream: Read + Write> Shared<Stream> {
+    /// Run a write operation with the shared state and publish its state transitions.
+    fn write_with<T, F>(&self, op: F) -> Result<T>
+    where
+        F: FnOnce(&mut WebSocketContext, &mut Stream) -> Result<T>,
+    {
+        let mut writer = self.writer.lock().unwrap();
+        self.write_locked(&mut writer, op)
+    }
+
+    fn write_locked<T, F>(&self, writer: &mut MutexGuard<'_, WriteHalf<Stream>>, op: F) -> Result<T>
+    where
+        F: FnOnce(&mut WebSocketContext, &mut Stream) -> Result<T>,
+    {
+        let WriteHalf { stream, context } = &mut **writer;
+        let before = self.state();
+        context.state = before;
+        let result = op(context, stream);
+        if context.state != before {
+            let mut state = self.state.lock().unwrap();
+            *state = merge(*state, context.state);
+        }
+        result
+    }
+
+    /// Send a pong or close reply queued by the reader.
+    fn reply(&self, frame: Frame) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        // A close frame written meanwhile replaces the reply, pongs are only sent while active.
+        let expected = match frame.header().opcode {
+            OpCode::Control(OpCtl::Close) => WebSocketState::ClosedByPeer,
+            _ => WebSocketState::Active,
+        };
+        if self.state() != expected {
+            return Ok(());
+        }
+        let result = self.write_locked(&mut writer, |context, stream| {
+            context.set_additional(frame);
+            context.flush(stream)
+        });
+        match result {
+            // Flushed by the next write, or the server terminated the connection after the reply.
+            Err(Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(Error::ConnectionClosed) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Combine the shared state with the state after a write: the writer only starts the close
+/// handshake or terminates the connection.
+fn merge(shared: WebSocketState, written: WebSocketState) -> WebSocketState {
+    match (shared, written) {
+        (WebSocketState::Terminated, _) | (_, WebSocketState::Terminated) => {
+            WebSocketState::Terminated
+        }
+        // Both sides sent a close frame.
+        (WebSocketState::ClosedByPeer, WebSocketState::ClosedByUs) => {
+            WebSocketState::CloseAcknowledged
+        }
+        (WebSocketState::Active, written) => written,
+        (shared, _) => shared,
+    }
+}
+
+/// The reading half of a WebSocket, see [`WebSocket::split`].
+#[derive(Debug)]
+pub struct WebSocketReader<Stream> {
+    stream: Stream,
+    context: WebSocketContext,
+    shared: Arc<Shared<Stream>>,
+    /// Whether the termination of the connection was returned by `read`.
+    reported_closed: bool,
+}
+
+impl<Stream> WebSocketReader<Stream> {
+    /// Returns a shared reference to the handle of the stream used for reading.
+    pub fn get_ref(&self) -> &Stream {
+        &self.stream
+    }
+
+    /// Read the configuration.
+    pub fn get_config(&self) -> &WebSocketConfig {
+        self.context.get_config()
+    }
+
+    /// Check if it is possible to read messages, see [`WebSocket::can_read`].
+    pub fn can_read(&self) -> bool {
+        self.shared.state().can_read()
+    }
+}
+
+impl<Stream: Read + Write> WebSocketReader<Stream> {
+    /// Read a message from the stream, see [`WebSocket::read`].
+    ///
+    /// Pongs and close replies are written by the writer half, waiting for a write in progress.
+    pub fn read(&mut self) -> Result<Message> {
+        loop {
+            let state = self.shared.state();
+            match state {
+                WebSocketState::Terminated if self.reported_closed => {
+                    return Err(Error::AlreadyClosed)
+                }
+                // The server closed the connection after replying to the close frame.
+                WebSocketState::Terminated => return Err(self.closed(None)),
+                // Servers don't wait for the client to close the TCP connection.
+                _ if self.context.role == Role::Server && !state.can_read() => {
+                    return Err(self.closed(Some(WebSocketState::Terminated)))
+                }
+                _ => {}
+            }
+
+            self.context.state = state;
+            let frame = self.context.read_frame(&mut self.stream)?;
+            let frame = match frame {
+                Some(frame) => frame,
+                None => {
+                    let previous = replace(
+                        &mut *self.shared.state.lock().unwrap(),
+                        WebSocketState::Terminated,
+                    );
+                    self.reported_closed = true;
+                    return match previous {
+                        WebSocketState::ClosedByPeer | WebSocketState::CloseAcknowledged => {
+                            Err(Error::ConnectionClosed)
+                        }
+                        _ => Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)),
+                    };
+                }
+            };
+
+            let (message, reply) = {
+                let mut state = self.shared.state.lock().unwrap();
+                self.context.state = *state;
+                let message = self.context.process_frame(frame);
+                *state = self.context.state;
+                (message, self.context.additional_send.take())
+            };
+            if let Some(reply) = reply {
+                self.shared.reply(reply)?;
+            }
+            if let Some(message) = message? {
+                return Ok(message);
+            }
+        }
+    }
+
+    fn closed(&mut self, state: Option<WebSocketState>) -> Error {
+        if let Some(state) = state {
+            *self.shared.state.lock().unwrap() = state;
+        }
+        self.reported_closed = true;
+        Error::ConnectionClosed
+    }
+}
+
+/// The writing half of a WebSocket, see [`WebSocket::split`].
+///
+/// Clones write to the same connection, each write is buffered as a whole.
+#[derive(Debug)]
+pub struct WebSocketWriter<Stream> {
+    shared: Arc<Shared<Stream>>,
+}
+
+impl<Stream> Clone for WebSocketWriter<Stream> {
+    fn clone(&self) -> Self {
+        WebSocketWriter { shared: self.shared.clone() }
+    }
+}
+
+impl<Stream> WebSocketWriter<Stream> {
+    /// Check if it is possible to write messages, see [`WebSocket::can_write`].
+    pub fn can_write(&self) -> bool {
+        self.shared.state().is_active()
+    }
+}
+
+impl<Stream: Read + Write> WebSocketWriter<Stream> {
+    /// Write a message, see [`WebSocket::write`].
+    pub fn write(&mut self, message: Message) -> Result<()> {
+        self.shared.write_with(|context, stream| context.write(stream, message))
+    }
+
+    /// Write and flush a message, see [`WebSocket::send`].
+    pub fn send(&mut self, message: Message) -> Result<()> {
+        self.shared.write_with(|context, stream| {
+            context.write(stream, message)?;
+            context.flush(stream)
+        })
+    }
+
+    /// Write a message encoded in advance, see [`WebSocket::write_prepared`].
+    pub fn write_prepared(&mut self, message: &PreparedMessage) -> Result<()> {
+        self.shared.write_with(|context, stream| context.write_prepared(stream, message))
+    }
+
+    /// Write and flush a message encoded in advance, see [`WebSocket::send_prepared`].
+    pub fn send_prepared(&mut self, message: &PreparedMessage) -> Result<()> {
+        self.shared.write_with(|context, stream| {
+            context.write_prepared(stream, message)?;
+            context.flush(stream)
+        })
+    }
+
+    /// Flush writes, see [`WebSocket::flush`].
+    pub fn flush(&mut self) -> Result<()> {
+        self.shared.write_with(|context, stream| context.flush(stream))
+    }
+
+    /// Start the close handshake, see [`WebSocket::close`]. The reader half receives the reply.
+    pub fn close(&mut self, code: Option<CloseFrame>) -> Result<()> {
+        self.shared.write_with(|context, stream| context.close(stream, code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, WebSocketState::*};
+
+    #[test]
+    fn merge_states() {
+        assert_eq!(merge(Active, ClosedByUs), ClosedByUs);
+        assert_eq!(merge(ClosedByPeer, ClosedByUs), CloseAcknowledged);
+        assert_eq!(merge(ClosedByPeer, Terminated), Terminated);
+        assert_eq!(merge(Terminated, ClosedByUs), Terminated);
+        assert_eq!(merge(CloseAcknowledged, ClosedByUs), CloseAcknowledged);
+    }
+}
@@ -0,0 +1,508 @@
+//! A single-threaded event loop serving many non-blocking connections with [`mio`].
+//!
+//! A [`Reactor`] owns listeners and [`WebSocket`]s over [`mio::net::TcpStream`]s. Each call to
+//! [`Reactor::poll`] waits for readiness events, accepts new connections, drives their server
+//! handshakes, reads the available messages, flushes pending writes and then passes the resulting
+//! [`Event`]s to a handler. The handler gets the reactor back to reply with [`Reactor::send`],
+//! close connections or register more of them.
+//!
+//! The sockets are never blocked on: a message that can't be written right away stays in the
+//! write buffer of its connection and is flushed once the socket is writable again.
+//!
+//! ```no_run
+//! use mio::net::TcpListener;
+//! use tungstenite::reactor::{Event, Reactor, ReactorConfig};
+//!
+//! let mut reactor = Reactor::new(ReactorConfig::default()).unwrap();
+//! reactor.listen(TcpListener::bind("127.0.0.1:9001".parse().unwrap()).unwrap()).unwrap();
+//! loop {
+//!     reactor
+//!         .poll(None, |reactor, event| match event {
+//!             Event::Message { id, message } if message.is_text() || message.is_binary() => {
+//!                 let _ = reactor.send(id, message);
+//!             }
+//!             Event::Closed { id, error: Some(err) } => eprintln!("{id:?} failed: {err}"),
+//!             _ => {}
+//!         })
+//!         .unwrap();
+//! }
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, ErrorKind},
+    result::Result as StdResult,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::*;
+use mio::{
+    net::{TcpListener, TcpStream},
+    Events, Interest, Poll, Token,
+};
+
+use crate::{
+    error::{Error, Result},
+    handshake::{
+        server::{Callback, ErrorResponse, Request, Response, ServerHandshake},
+        HandshakeConfig, HandshakeError, MidHandshake,
+    },
+    protocol::{CloseFrame, Message, PreparedMessage, WebSocket, WebSocketConfig},
+};
+
+/// The configuration of a [`Reactor`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReactorConfig {
+    /// The configuration of the accepted connections, `None` uses the default configuration.
+    pub websocket: Option<WebSocketConfig>,
+    /// The limits enforced while reading handshake requests. Connections that don't complete the
+    /// handshake within its timeout are dropped.
+    pub handshake: Option<HandshakeConfig>,
+    /// The maximum number of readiness events handled per call to [`Reactor::poll`].
+    /// The default value is 1024.
+    pub events_capacity: usize,
+    /// The maximum number of messages read from one connection per call to [`Reactor::poll`],
+    /// so a busy connection can't starve the others. The remaining messages are read by the
+    /// next call. The default value is 64.
+    pub max_messages_per_poll: usize,
+}
+
+impl Default for ReactorConfig {
+    fn default() -> Self {
+        ReactorConfig {
+            websocket: None,
+            handshake: None,
+            events_capacity: 1024,
+            max_messages_per_poll: 64,
+        }
+    }
+}
+
+/// Identifies a connection of a [`Reactor`]. Identifiers are not reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(usize);
+
+/// What happened to a connection of a [`Reactor`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Event {
+    /// An accepted connection completed the handshake.
+    Open {
+        /// The new connection.
+        id: ConnectionId,
+        /// The handshake request of the client.
+        request: Request,
+    },
+    /// A message was received.
+    Message {
+        /// The connection the message was received on.
+        id: ConnectionId,
+        /// The message.
+        message: Message,
+    },
+    /// The connection was closed and removed from the reactor.
+    Closed {
+        /// The removed connection.
+        id: ConnectionId,
+        /// `None` if the connection was closed after a close handshake, otherwise the error that
+        /// terminated it.
+        error: Option<Error>,
+    },
+}
+
+/// Remembers the request of a server handshake for [`Event::Open`].
+struct Capture(Arc<Mutex<Option<Request>>>);
+
+impl Callback for Capture {
+    fn on_request(
+        self,
+        request: &Request,
+        response: Response,
+    ) -> StdResult<Response, ErrorResponse> {
+        *self.0.lock().unwrap() = Some(request.clone());
+        Ok(response)
+    }
+}
+
+struct Handshake {
+    machine: MidHandshake<ServerHandshake<TcpStream, Capture>>,
+    request: Arc<Mutex<Option<Request>>>,
+}
+
+enum Entry {
+    Listener(TcpListener),
+    Handshake(Handshake),
+    Open(WebSocket<TcpStream>),
+}
+
+/// An event loop driving listeners and WebSocket connections on readiness events.
+///
+/// See the [module documentation](self).
+pub struct Reactor {
+    poll: Poll,
+    events: Events,
+    config: ReactorConfig,
+    entries: HashMap<usize, Entry>,
+    next_token: usize,
+    /// Events produced outside of the readiness handling, e.g. by failed sends.
+    pending: VecDeque<Event>,
+    /// Connections that stopped reading at `max_messages_per_poll` messages. No readiness event
+    /// is raised for the data they already received, so they are read again by the next poll.
+    unfinished: Vec<usize>,
+}
+
+impl fmt::Debug for Reactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reactor")
+            .field("config", &self.config)
+            .field("connections", &self.connection_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Reactor {
+    /// Create a reactor without listeners and connections.
+    pub fn new(config: ReactorConfig) -> Result<Self> {
+        Ok(Reactor {
+            poll: Poll::new()?,
+            events: Events::with_capacity(config.events_capacity.max(1)),
+            config,
+            entries: HashMap::new(),
+            next_token: 0,
+            pending: VecDeque::new(),
+            unfinished: Vec::new(),
+        })
+    }
+
+    fn next_token(&mut self) -> usize {
+        let token = self.next_token;
+        self.next_token += 1;
+        token
+    }
+
+    /// Accept connections of `listener`. The server handshakes of accepted connections are
+    /// performed by the reactor, [`Event::Open`] reports the completed ones.
+    pub fn listen(&mut self, mut listener: TcpListener) -> Result<()> {
+        let token = self.next_token();
+        self.poll.registry().register(&mut listener, Token(token), Interest::READABLE)?;
+        self.entries.insert(token, Entry::Listener(listener));
+        Ok(())
+    }
+
+    /// Add a connection that completed its handshake, e.g. a client connection.
+    ///
+    /// No [`Event::Open`] is reported for it. Messages already received are read on the next
+    /// [`poll`](Self::poll).
+    pub fn register(&mut self, mut socket: WebSocket<TcpStream>) -> Result<ConnectionId> {
+        let token = self.next_token();
+        self.poll.registry().register(
+            socket.get_mut(),
+            Token(token),
+            Interest::READABLE | Interest::WRITABLE,
+        )?;
+        self.entries.insert(token, Entry::Open(socket));
+        // Data received before the registration doesn't raise a readiness event.
+        let events = self.read(token);
+        self.pending.extend(events);
+        Ok(ConnectionId(token))
+    }
+
+    /// Remove a connection from the reactor without closing it.
+    pub fn remove(&mut self, id: ConnectionId) -> Option<WebSocket<TcpStream>> {
+        match self.entries.remove(&id.0) {
+            Some(Entry::Open(mut socket)) => {
+                let _ = self.poll.registry().deregister(socket.get_mut());
+                Some(socket)
+            }
+            Some(entry) => {
+                self.entries.insert(id.0, entry);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// The connection `id`, if it is open.
+    pub fn get_mut(&mut self, id: ConnectionId) -> Option<&mut WebSocket<TcpStream>> {
+        match self.entries.get_mut(&id.0) {
+            Some(Entry::Open(socket)) => Some(socket),
+            _ => None,
+        }
+    }
+
+    /// The number of open connections, not counting the ones still performing the handshake.
+    pub fn connection_count(&self) -> usize {
+        self.entries.values().filter(|entry| matches!(entry, Entry::Open(_))).count()
+    }
+
+    /// Write a message to the connection `id` and flush as much as the socket accepts.
+    ///
+    /// Fails with [`Error::AlreadyClosed`] if there is no open connection `id`, and like
+    /// [`WebSocket::write`] if the message can't be written, e.g. with [`Error::WriteBufferFull`].
+    /// I/O errors close the connection and are reported by [`Event::Closed`] instead.
+    pub fn send(&mut self, id: ConnectionId, message: Message) -> Result<()> {
+        self.write_with(id, |socket| socket.write(message))
+    }
+
+    /// Write a prepared message to the connection `id`, see [`send`](Self::send).
+    pub fn send_prepared(&mut self, id: ConnectionId, message: &PreparedMessage) -> Result<()> {
+        self.write_with(id, |socket| socket.write_prepared(message))
+    }
+
+    /// Start the close handshake of the connection `id`. It is removed once the peer replied,
+    /// which [`Event::Closed`] reports.
+    pub fn close(&mut self, id: ConnectionId, frame: Option<CloseFrame>) -> Result<()> {
+        self.write_with(id, |socket| socket.close(frame))
+    }
+
+    fn write_with<F>(&mut self, id: ConnectionId, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut WebSocket<TcpStream>) -> Result<()>,
+    {
+        let socket = self.get_mut(id).ok_or(Error::AlreadyClosed)?;
+        let result = match write(&mut *socket) {
+            Ok(()) => socket.flush(),
+            Err(err @ (Error::Io(_) | Error::ConnectionClosed)) => Err(err),
+            Err(err) => return Err(err),
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(Error::ConnectionClosed) => {
+                self.remove_entry(id.0);
+                self.pending.push_back(Event::Closed { id, error: None });
+                Ok(())
+            }
+            Err(err) => {
+                self.remove_entry(id.0);
+                self.pending.push_back(Event::Closed { id, error: Some(err) });
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for readiness events, handle them and pass the resulting events to
+    /// `handler`. `None` waits until there is an event.
+    ///
+    /// Returns when the events are handled, even if none of them produced an [`Event`], so the
+    /// caller should call `poll` in a loop. Fails only if polling fails, errors of single
+    /// connections are reported by [`Event::Closed`].
+    pub fn poll<F>(&mut self, timeout: Option<Duration>, mut handler: F) -> Result<()>
+    where
+        F: FnMut(&mut Reactor, Event),
+    {
+        let timeout = if self.pending.is_empty() && self.unfinished.is_empty() {
+            self.expire_handshakes();
+            min_timeout(timeout, self.next_handshake_deadline())
+        } else {
+            Some(Duration::ZERO)
+        };
+        match self.poll.poll(&mut self.events, timeout) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut tokens = std::mem::take(&mut self.unfinished);
+        tokens.extend(self.events.iter().map(|event| event.token().0));
+        tokens.sort_unstable();
+        tokens.dedup();
+        for token in tokens {
+            self.ready(token);
+        }
+        self.expire_handshakes();
+
+        while let Some(event) = self.pending.pop_front() {
+            handler(self, event);
+        }
+        Ok(())
+    }
+
+    /// Handle a readiness event of the entry `token`.
+    fn ready(&mut self, token: usize) {
+        match self.entries.get(&token) {
+            Some(Entry::Listener(_)) => self.accept(token),
+            Some(Entry::Handshake(_)) => self.drive_handshake(token),
+            Some(Entry::Open(_)) => {
+                let events = self.read(token);
+                self.pending.extend(events);
+            }
+            // Removed while handling an earlier event.
+            None => {}
+        }
+    }
+
+    fn accept(&mut self, token: usize) {
+        loop {
+            let accepted = match self.entries.get(&token) {
+                Some(Entry::Listener(listener)) => listener.accept(),
+                _ => return,
+            };
+            let mut stream = match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) if is_transient(&err) => continue,
+                Err(err) => {
+                    warn!("Failed to accept a connection: {err}");
+                    return;
+                }
+            };
+            let connection = self.next_token();
+            let registered = self.poll.registry().register(
+                &mut stream,
+                Token(connection),
+                Interest::READABLE | Interest::WRITABLE,
+            );
+            if let Err(err) = registered {
+                warn!("Failed to register a connection: {err}");
+                continue;
+            }
+            let request = Arc::new(Mutex::new(None));
+            let machine = ServerHandshake::start_with_handshake_config(
+                stream,
+                Capture(request.clone()),
+                self.config.websocket,
+                self.config.handshake,
+            );
+            self.entries.insert(connection, Entry::Handshake(Handshake { machine, request }));
+            self.drive_handshake(connection);
+        }
+    }
+
+    fn drive_handshake(&mut self, token: usize) {
+        let Handshake { machine, request } = match self.entries.remove(&token) {
+            Some(Entry::Handshake(handshake)) => handshake,
+            Some(entry) => {
+                self.entries.insert(token, entry);
+                return;
+            }
+            None => return,
+        };
+        match machine.handshake() {
+            Ok(socket) => {
+                let request = request.lock().unwrap().take().expect("the request was handled");
+                let id = ConnectionId(token);
+                self.entries.insert(token, Entry::Open(socket));
+                self.pending.push_back(Event::Open { id, request });
+                // The client may have sent messages right after its request.
+                let events = self.read(token);
+                self.pending.extend(events);
+            }
+            Err(HandshakeError::Interrupted(machine)) => {
+                self.entries.insert(token, Entry::Handshake(Handshake { machine, request }));
+            }
+            Err(HandshakeError::Failure(err)) => debug!("WebSocket handshake failed: {err}"),
+        }
+    }
+
+    /// Drop the connections that didn't complete the handshake in time.
+    fn expire_handshakes(&mut self) {
+        let expired: Vec<usize> = self
+            .entries
+            .iter()
+            .filter_map(|(token, entry)| match entry {
+                Entry::Handshake(h) if h.machine.remaining_time() == Some(Duration::ZERO) => {
+                    Some(*token)
+                }
+                _ => None,
+            })
+            .collect();
+        for token in expired {
+            debug!("WebSocket handshake timed out");
+            self.remove_entry(token);
+        }
+    }
+
+    fn next_handshake_deadline(&self) -> Option<Duration> {
+        self.entries
+            .values()
+            .filter_map(|entry| match entry {
+                Entry::Handshake(h) => h.machine.remaining_time(),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Read the available messages of the open connection `token`, up to
+    /// `max_messages_per_poll`, and flush its replies.
+    fn read(&mut self, token: usize) -> Vec<Event> {
+        let id = ConnectionId(token);
+        let limit = self.config.max_messages_per_poll.max(1);
+        let mut events = Vec::new();
+        let socket = match self.entries.get_mut(&token) {
+            Some(Entry::Open(socket)) => socket,
+            _ => return events,
+        };
+        let mut unfinished = false;
+        let result = loop {
+            if events.len() == limit {
+                unfinished = true;
+                break socket.flush();
+            }
+            match socket.read() {
+                Ok(message) => events.push(Event::Message { id, message }),
+                Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => break socket.flush(),
+                Err(err) => break Err(err),
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => {
+                let error = match err {
+                    Error::ConnectionClosed => None,
+                    err => Some(err),
+                };
+                self.remove_entry(token);
+                events.push(Event::Closed { id, error });
+                return events;
+            }
+        }
+        if unfinished {
+            self.unfinished.push(token);
+        }
+        events
+    }
+
+    fn remove_entry(&mut self, token: usize) {
+        let registry = self.poll.registry();
+        let _ = match self.entries.remove(&token) {
+            Some(Entry::Listener(mut listener)) => registry.deregister(&mut listener),
+            Some(Entry::Handshake(mut h)) => registry.deregister(h.machine.get_mut().get_mut()),
+            Some(Entry::Open(mut socket)) => registry.deregister(socket.get_mut()),
+            None => Ok(()),
+        };
+    }
+}
+
+/// Errors of `accept` that concern only the connection being accepted.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::Interrupted
+    )
+}
+
+fn min_timeout(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::min_timeout;
+    use std::time::Duration;
+
+    #[test]
+    fn timeouts() {
+        let (short, long) = (Duration::from_millis(1), Duration::from_secs(1));
+        assert_eq!(min_timeout(None, None), None);
+        assert_eq!(min_timeout(Some(long), None), Some(long));
+        assert_eq!(min_timeout(None, Some(short)), Some(short));
+        assert_eq!(min_timeout(Some(long), Some(short)), Some(short));
+    }
+}
@@ -0,0 +1,343 @@
+//! A client that reconnects when its connection is lost.
+//!
+//! [`ReconnectingClient`] wraps [`connect_with_options`] and dials again with exponential backoff
+//! when the connection fails or is closed by the server, rotating through a list of endpoints.
+//! Close codes [`CloseCode::Restart`] and [`CloseCode::Again`] are honoured: a restarting server
+//! is reconnected to after a randomized delay, an overloaded one is skipped for the next
+//! endpoint if there is one.
+//!
+//! Messages are not buffered or replayed across connections. Use
+//! [`ReconnectingClient::set_on_connect`] to restore the state of the session, e.g. to resend
+//! subscriptions, after every connect.
+
+use std::{fmt, io::ErrorKind, net::TcpStream, ops::Range, thread, time::Duration};
+
+use log::*;
+use rand::Rng;
+
+use crate::{
+    client::{connect_with_options, ConnectOptions, IntoClientRequest},
+    error::{Error, Result},
+    handshake::client::{generate_key, Request, Response},
+    protocol::{frame::coding::CloseCode, CloseFrame, Message, WebSocket},
+    stream::MaybeTlsStream,
+};
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+type OnConnect = Box<dyn FnMut(&mut Socket, &Response) -> Result<()> + Send>;
+
+/// When and how often [`ReconnectingClient`] dials again.
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// The delay before the first retry after a failed attempt or a lost connection. The default
+    /// value is 500 milliseconds.
+    pub initial_delay: Duration,
+    /// The upper bound for the delay between attempts. The default value is 30 seconds.
+    pub max_delay: Duration,
+    /// The factor the delay grows by after each failed attempt. The default value is 2.
+    pub multiplier: f64,
+    /// The fraction of the delay that is randomized, between 0 and 1. A delay `d` becomes a
+    /// random delay between `d * (1 - jitter)` and `d`. The default value is 0.5.
+    pub jitter: f64,
+    /// The maximum number of consecutive failed attempts before the error of the last attempt is
+    /// returned, `None` means retrying forever. The default value is `None`.
+    pub max_attempts: Option<u32>,
+    /// The range of the randomized delay before reconnecting to a server that closed the
+    /// connection with [`CloseCode::Restart`] or [`CloseCode::Again`]. The default value is 5 to
+    /// 30 seconds as recommended for `Restart`.
+    pub restart_delay: Range<Duration>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: None,
+            restart_delay: Duration::from_secs(5)..Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// The delay before the attempt following `failures` consecutive failed ones.
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(failures.saturating_sub(1) as i32);
+        let delay = self.initial_delay.as_secs_f64() * factor;
+        let delay = delay.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let delay = delay * (1.0 - jitter * rand::thread_rng().gen::<f64>());
+        Duration::from_secs_f64(delay)
+    }
+
+    /// The randomized delay before reconnecting to a restarting or overloaded server.
+    fn restart_delay(&self) -> Duration {
+        if self.restart_delay.is_empty() {
+            self.restart_delay.start
+        } else {
+            rand::thread_rng().gen_range(self.restart_delay.clone())
+        }
+    }
+}
+
+/// A WebSocket client that reconnects when its connection is lost.
+///
+/// The connection is established lazily by the first call to [`read`](Self::read),
+/// [`send`](Self::send) or [`connect`](Self::connect). Any error while reading, as well as the
+/// server closing the connection, makes the next call reconnect. A failed `send` is returned to
+/// the caller and the message is not retried. After [`close`](Self::close) the client does not
+/// reconnect anymore.
+///
+/// ```no_run
+/// use tungstenite::{
+///     client::ConnectOptions,
+///     reconnect::{ReconnectConfig, ReconnectingClient},
+///     Message,
+/// };
+///
+/// let endpoints = ["ws://primary.example.com/feed", "ws://backup.example.com/feed"];
+/// let mut client =
+///     ReconnectingClient::new(endpoints, ConnectOptions::default(), ReconnectConfig::default())
+///         .unwrap();
+/// client.set_on_connect(|socket, _response| socket.send(Message::text("subscribe:prices")));
+/// loop {
+///     let msg = client.read().unwrap();
+///     println!("{msg}");
+/// }
+/// ```
+pub struct ReconnectingClient {
+    endpoints: Vec<Request>,
+    endpoint: usize,
+    options: ConnectOptions,
+    config: ReconnectConfig,
+    socket: Option<Socket>,
+    on_connect: Option<OnConnect>,
+    /// Consecutive failed connection attempts.
+    failures: u32,
+    /// The delay before the next connection attempt.
+    delay: Option<Duration>,
+    closed: bool,
+}
+
+impl fmt::Debug for ReconnectingClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectingClient")
+            .field("endpoints", &self.endpoints)
+            .field("endpoint", &self.endpoint)
+            .field("options", &self.options)
+            .field("config", &self.config)
+            .field("socket", &self.socket)
+            .field("failures", &self.failures)
+            .field("delay", &self.delay)
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReconnectingClient {
+    /// Create a client for the given endpoints, tried in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `endpoints` is empty.
+    pub fn new<I>(endpoints: I, options: ConnectOptions, config: ReconnectConfig) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: IntoClientRequest,
+    {
+        let endpoints = endpoints
+            .into_iter()
+            .map(IntoClientRequest::into_client_request)
+            .collect::<Result<Vec<_>>>()?;
+        assert!(!endpoints.is_empty(), "ReconnectingClient needs at least one endpoint");
+        Ok(ReconnectingClient {
+            endpoints,
+            endpoint: 0,
+            options,
+            config,
+            socket: None,
+            on_connect: None,
+            failures: 0,
+            delay: None,
+            closed: false,
+        })
+    }
+
+    /// Set a hook called with every newly established connection, e.g. to authenticate or to
+    /// resend subscriptions. An error returned by the hook counts as a failed connection attempt.
+    pub fn set_on_connect<F>(&mut self, on_connect: F)
+    where
+        F: FnMut(&mut Socket, &Response) -> Result<()> + Send + 'static,
+    {
+        self.on_connect = Some(Box::new(on_connect));
+    }
+
+    /// Returns the current connection, if any.
+    pub fn get_ref(&self) -> Option<&Socket> {
+        self.socket.as_ref()
+    }
+
+    /// Returns the current connection mutably, if any.
+    pub fn get_mut(&mut self) -> Option<&mut Socket> {
+        self.socket.as_mut()
+    }
+
+    /// The index of the endpoint currently used or tried next.
+    pub fn endpoint(&self) -> usize {
+        self.endpoint
+    }
+
+    /// Returns the current connection, connecting first if there is none.
+    ///
+    /// Blocks until connected, or returns the last error once
+    /// [`max_attempts`](ReconnectConfig::max_attempts) consecutive attempts failed. After
+    /// [`close`](Self::close) the connection being closed is still returned, but no new one is
+    /// made.
+    pub fn connect(&mut self) -> Result<&mut Socket> {
+        if self.socket.is_none() {
+            if self.closed {
+                return Err(Error::AlreadyClosed);
+            }
+            let socket = self.dial()?;
+            self.socket = Some(socket);
+        }
+        Ok(self.socket.as_mut().expect("Bug: no socket after connecting"))
+    }
+
+    fn dial(&mut self) -> Result<Socket> {
+        loop {
+            if let Some(delay) = self.delay.take() {
+                debug!("Reconnecting in {:?}", delay);
+                thread::sleep(delay);
+            }
+
+            let mut request = self.endpoints[self.endpoint].clone();
+            // Every connection needs a fresh key.
+            if let Some(key) = request.headers_mut().get_mut("Sec-WebSocket-Key") {
+                *key = generate_key().parse()?;
+            }
+            debug!("Connecting to {}", request.uri());
+
+            let result = connect_with_options(request, self.options.clone()).and_then(
+                |(mut socket, response)| match self.on_connect.as_mut() {
+                    Some(on_connect) => on_connect(&mut socket, &response).map(|_| socket),
+                    None => Ok(socket),
+                },
+            );
+            match result {
+                Ok(socket) => {
+                    self.failures = 0;
+                    return Ok(socket);
+                }
+                Err(err) => {
+                    warn!("Failed to connect to endpoint {}: {}", self.endpoint, err);
+                    self.failures += 1;
+                    if self.config.max_attempts.map_or(false, |max| self.failures >= max) {
+                        self.failures = 0;
+                        return Err(err);
+                    }
+                    self.endpoint = (self.endpoint + 1) % self.endpoints.len();
+                    self.delay = Some(self.config.backoff(self.failures));
+                }
+            }
+        }
+    }
+
+    /// Forget the current connection and schedule the next attempt according to how it ended.
+    fn disconnected(&mut self, close: Option<CloseCode>) {
+        self.socket = None;
+        self.delay = match close {
+            Some(CloseCode::Again) if self.endpoints.len() > 1 => {
+                self.endpoint = (self.endpoint + 1) % self.endpoints.len();
+                None
+            }
+            Some(CloseCode::Restart) | Some(CloseCode::Again) => Some(self.config.restart_delay()),
+            _ => Some(self.config.backoff(1)),
+        };
+    }
+
+    /// Read a message, reconnecting first if needed.
+    ///
+    /// Close messages of the server are returned, the following call reconnects.
+    pub fn read(&mut self) -> Result<Message> {
+        loop {
+            let socket = self.connect()?;
+            match socket.read() {
+                Ok(Message::Close(frame)) => {
+                    let code = frame.as_ref().map(|frame| frame.code);
+                    // Complete the closing handshake, the reply is queued by `read`.
+                    let _ = socket.flush();
+                    if !self.closed {
+                        self.disconnected(code);
+                    }
+                    return Ok(Message::Close(frame));
+                }
+                Ok(msg) => return Ok(msg),
+                Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                    return Err(Error::Io(err))
+                }
+                Err(err) if self.closed => {
+                    self.socket = None;
+                    return Err(err);
+                }
+                Err(err) => {
+                    debug!("Connection lost: {}", err);
+                    self.disconnected(None);
+                }
+            }
+        }
+    }
+
+    /// Send a message, connecting first if needed.
+    ///
+    /// If sending fails the connection is dropped, the error is returned and the message is not
+    /// sent again.
+    pub fn send(&mut self, message: Message) -> Result<()> {
+        let result = self.connect()?.send(message);
+        if let Err(ref err) = result {
+            if !matches!(err, Error::Io(err) if err.kind() == ErrorKind::WouldBlock) {
+                self.disconnected(None);
+            }
+        }
+        result
+    }
+
+    /// Close the current connection and stop reconnecting.
+    ///
+    /// Keep calling [`read`](Self::read) to complete the close handshake: it returns the close
+    /// reply of the server, then [`Error::ConnectionClosed`].
+    pub fn close(&mut self, code: Option<CloseFrame<'_>>) -> Result<()> {
+        self.closed = true;
+        match self.socket.as_mut() {
+            Some(socket) => socket.close(code),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let config = ReconnectConfig {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: 0.0,
+            ..ReconnectConfig::default()
+        };
+        let delays: Vec<_> = (1..7).map(|failures| config.backoff(failures).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+
+        let config = ReconnectConfig { jitter: 0.5, ..config };
+        for _ in 0..100 {
+            let delay = config.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+            let delay = config.restart_delay();
+            assert!(config.restart_delay.contains(&delay));
+        }
+    }
+}
@@ -1,10 +1,30 @@
 //! Methods to accept an incoming WebSocket connection on a server.
+//!
+//! Any `Read + Write` stream can be accepted, e.g. connections of a Unix domain socket which
+//! clients reach with a `ws+unix:///path/to.sock:/resource` URL:
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() {
+//! use std::os::unix::net::UnixListener;
+//! use tungstenite::accept;
+//!
+//! let listener = UnixListener::bind("/tmp/websocket.sock").unwrap();
+//! for stream in listener.incoming() {
+//!     let mut websocket = accept(stream.unwrap()).unwrap();
+//!     let msg = websocket.read().unwrap();
+//!     websocket.send(msg).unwrap();
+//! }
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
 
 pub use crate::handshake::server::ServerHandshake;
 
 use crate::handshake::{
     server::{Callback, NoCallback},
-    HandshakeError,
+    HandshakeConfig, HandshakeError,
 };
 
 use crate::protocol::{WebSocket, WebSocketConfig};
@@ -52,7 +72,7 @@ pub fn accept_hdr_with_config<S: Read + Write, C: Callback>(
     callback: C,
     config: Option<WebSocketConfig>,
 ) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, C>>> {
-    ServerHandshake::start(stream, callback, config).handshake()
+    accept_hdr_with_handshake_config(stream, callback, config, None)
 }
 
 /// Accept the given Stream as a WebSocket.
@@ -66,3 +86,35 @@ pub fn accept_hdr<S: Read + Write, C: Callback>(
 ) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, C>>> {
     accept_hdr_with_config(stream, callback, None)
 }
+
+/// Accept the given Stream as a WebSocket.
+///
+/// Uses the configurations provided as arguments. Calling it with `None` for `handshake_config`
+/// is equal to calling `accept_with_config()`.
+///
+/// The handshake configuration controls the limits enforced while reading the HTTP request,
+/// see [`HandshakeConfig`] for details.
+pub fn accept_with_handshake_config<S: Read + Write>(
+    stream: S,
+    config: Option<WebSocketConfig>,
+    handshake_config: Option<HandshakeConfig>,
+) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, NoCallback>>> {
+    accept_hdr_with_handshake_config(stream, NoCallback, config, handshake_config)
+}
+
+/// Accept the given Stream as a WebSocket.
+///
+/// Uses the configurations provided as arguments. Calling it with `None` for `handshake_config`
+/// is equal to calling `accept_hdr_with_config()`.
+///
+/// This function does the same as `accept_with_handshake_config()` but accepts an extra callback
+/// for header processing.
+pub fn accept_hdr_with_handshake_config<S: Read + Write, C: Callback>(
+    stream: S,
+    callback: C,
+    config: Option<WebSocketConfig>,
+    handshake_config: Option<HandshakeConfig>,
+) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, C>>> {
+    ServerHandshake::start_with_handshake_config(stream, callback, config, handshake_config)
+        .handshake()
+}
@@ -4,6 +4,8 @@
 //! `native_tls` or `openssl` will work as long as there is a TLS stream supporting standard
 //! `Read + Write` traits.
 
+#[cfg(any(feature = "native-tls", feature = "openssl"))]
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 #[cfg(feature = "__rustls-tls")]
 use std::ops::Deref;
 use std::{
@@ -12,6 +14,8 @@ use std::{
 };
 
 use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 
 #[cfg(feature = "native-tls")]
 use native_tls_crate::TlsStream;
@@ -39,6 +43,14 @@ impl NoDelay for TcpStream {
     }
 }
 
+/// Unix domain sockets have no Nagle algorithm, this is a no-op.
+#[cfg(unix)]
+impl NoDelay for UnixStream {
+    fn set_nodelay(&mut self, _nodelay: bool) -> IoResult<()> {
+        Ok(())
+    }
+}
+
 #[cfg(feature = "native-tls")]
 impl<S: Read + Write + NoDelay> NoDelay for TlsStream<S> {
     fn set_nodelay(&mut self, nodelay: bool) -> IoResult<()> {
@@ -46,6 +58,13 @@ impl<S: Read + Write + NoDelay> NoDelay for TlsStream<S> {
     }
 }
 
+#[cfg(feature = "openssl")]
+impl<S: Read + Write + NoDelay> NoDelay for openssl::ssl::SslStream<S> {
+    fn set_nodelay(&mut self, nodelay: bool) -> IoResult<()> {
+        self.get_mut().set_nodelay(nodelay)
+    }
+}
+
 #[cfg(feature = "__rustls-tls")]
 impl<S, SD, T> NoDelay for StreamOwned<S, T>
 where
@@ -58,6 +77,25 @@ where
     }
 }
 
+/// Trait to create a second handle to a stream, for [`WebSocket::split`](crate::WebSocket::split).
+pub trait TryClone: Sized {
+    /// Create a handle reading from and writing to the same stream.
+    fn try_clone(&self) -> IoResult<Self>;
+}
+
+impl TryClone for TcpStream {
+    fn try_clone(&self) -> IoResult<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl TryClone for UnixStream {
+    fn try_clone(&self) -> IoResult<Self> {
+        UnixStream::try_clone(self)
+    }
+}
+
 /// A stream that might be protected with TLS.
 #[non_exhaustive]
 pub enum MaybeTlsStream<S: Read + Write> {
@@ -66,68 +104,394 @@ pub enum MaybeTlsStream<S: Read + Write> {
     #[cfg(feature = "native-tls")]
     /// Encrypted socket stream using `native-tls`.
     NativeTls(native_tls_crate::TlsStream<S>),
+    #[cfg(feature = "native-tls")]
+    /// A `native-tls` handshake interrupted because the socket would block. It is resumed by
+    /// the next read or write, turning the stream into [`MaybeTlsStream::NativeTls`] once done.
+    NativeTlsHandshake(NativeTlsHandshake<S>),
+    #[cfg(feature = "openssl")]
+    /// Encrypted socket stream using `openssl`.
+    OpenSsl(openssl::ssl::SslStream<S>),
+    #[cfg(feature = "openssl")]
+    /// An `openssl` handshake interrupted because the socket would block. It is resumed by the
+    /// next read or write, turning the stream into [`MaybeTlsStream::OpenSsl`] once done.
+    OpenSslHandshake(OpenSslHandshake<S>),
     #[cfg(feature = "__rustls-tls")]
     /// Encrypted socket stream using `rustls`.
     Rustls(rustls::StreamOwned<rustls::ClientConnection, S>),
+    #[cfg(feature = "__rustls-tls")]
+    /// Encrypted socket stream of a server using `rustls`.
+    RustlsServer(rustls::StreamOwned<rustls::ServerConnection, S>),
+    #[cfg(unix)]
+    /// Unix domain socket stream (`ws+unix://` URL).
+    Unix(UnixStream),
+    /// Encrypted socket stream using a TLS library not supported by this crate, usually created
+    /// by a [`TlsConnect`](crate::TlsConnect) implementation.
+    Custom(Box<dyn CustomTlsStream<S> + Send + Sync>),
+}
+
+/// A TLS stream of a TLS library not supported by this crate, see [`MaybeTlsStream::Custom`].
+///
+/// A non-blocking handshake may be completed lazily by the first reads and writes, returning
+/// [`WouldBlock`](std::io::ErrorKind::WouldBlock) errors until it is done.
+pub trait CustomTlsStream<S>: Read + Write {
+    /// Returns a shared reference to the underlying stream.
+    fn get_ref(&self) -> &S;
+
+    /// Returns a mutable reference to the underlying stream.
+    fn get_mut(&mut self) -> &mut S;
+
+    /// Returns the details of the TLS session, see [`MaybeTlsStream::tls_info`]. The default
+    /// implementation returns `None`.
+    fn tls_info(&self) -> Option<TlsInfo> {
+        None
+    }
+}
+
+/// A `native-tls` client or server handshake in progress.
+#[cfg(feature = "native-tls")]
+pub struct NativeTlsHandshake<S>(Option<native_tls_crate::MidHandshakeTlsStream<S>>);
+
+#[cfg(feature = "native-tls")]
+impl<S> NativeTlsHandshake<S> {
+    #[cfg(feature = "handshake")]
+    pub(crate) fn new(stream: native_tls_crate::MidHandshakeTlsStream<S>) -> Self {
+        NativeTlsHandshake(Some(stream))
+    }
+
+    /// Returns a shared reference to the inner stream, `None` if the handshake failed.
+    pub fn get_ref(&self) -> Option<&S> {
+        self.0.as_ref().map(|stream| stream.get_ref())
+    }
+
+    /// Returns a mutable reference to the inner stream, `None` if the handshake failed.
+    pub fn get_mut(&mut self) -> Option<&mut S> {
+        self.0.as_mut().map(|stream| stream.get_mut())
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl<S: Debug> Debug for NativeTlsHandshake<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NativeTlsHandshake").field(&self.0).finish()
+    }
+}
+
+/// An `openssl` client or server handshake in progress.
+#[cfg(feature = "openssl")]
+pub struct OpenSslHandshake<S>(Option<openssl::ssl::MidHandshakeSslStream<S>>);
+
+#[cfg(feature = "openssl")]
+impl<S> OpenSslHandshake<S> {
+    #[cfg(feature = "handshake")]
+    pub(crate) fn new(stream: openssl::ssl::MidHandshakeSslStream<S>) -> Self {
+        OpenSslHandshake(Some(stream))
+    }
+
+    /// Returns a shared reference to the inner stream, `None` if the handshake failed.
+    pub fn get_ref(&self) -> Option<&S> {
+        self.0.as_ref().map(|stream| stream.get_ref())
+    }
+
+    /// Returns a mutable reference to the inner stream, `None` if the handshake failed.
+    pub fn get_mut(&mut self) -> Option<&mut S> {
+        self.0.as_mut().map(|stream| stream.get_mut())
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl<S: Debug> Debug for OpenSslHandshake<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OpenSslHandshake").field(&self.0).finish()
+    }
+}
+
+impl<S: Read + Write> MaybeTlsStream<S> {
+    /// Continue an interrupted TLS handshake, if any.
+    fn complete_handshake(&mut self) -> IoResult<()> {
+        match self {
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(NativeTlsHandshake(stream)) => {
+                use native_tls_crate::HandshakeError;
+
+                let result = stream
+                    .take()
+                    .ok_or_else(|| IoError::new(IoErrorKind::NotConnected, "TLS handshake failed"))?
+                    .handshake();
+                match result {
+                    Ok(tls) => *self = MaybeTlsStream::NativeTls(tls),
+                    Err(HandshakeError::WouldBlock(mid)) => {
+                        *stream = Some(mid);
+                        return Err(IoErrorKind::WouldBlock.into());
+                    }
+                    Err(HandshakeError::Failure(err)) => {
+                        return Err(IoError::new(IoErrorKind::Other, err))
+                    }
+                }
+            }
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(OpenSslHandshake(stream)) => {
+                use openssl::ssl::HandshakeError;
+
+                let result = stream
+                    .take()
+                    .ok_or_else(|| IoError::new(IoErrorKind::NotConnected, "TLS handshake failed"))?
+                    .handshake();
+                match result {
+                    Ok(tls) => *self = MaybeTlsStream::OpenSsl(tls),
+                    Err(HandshakeError::WouldBlock(mid)) => {
+                        *stream = Some(mid);
+                        return Err(IoErrorKind::WouldBlock.into());
+                    }
+                    Err(HandshakeError::Failure(mid)) => {
+                        return Err(IoError::new(IoErrorKind::Other, mid.into_error()))
+                    }
+                    Err(HandshakeError::SetupFailure(err)) => {
+                        let err = openssl::ssl::Error::from(err);
+                        return Err(IoError::new(IoErrorKind::Other, err));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Details of an established TLS session, see [`MaybeTlsStream::tls_info`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TlsInfo {
+    /// The negotiated protocol version, e.g. `TLSv1_3`. Not available with `native-tls`.
+    pub version: Option<String>,
+    /// The name of the negotiated cipher suite as used by the TLS library, e.g.
+    /// `TLS13_AES_256_GCM_SHA384` with `rustls`. Not available with `native-tls`.
+    pub cipher_suite: Option<String>,
+    /// The protocol negotiated by ALPN, if any.
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// The DER-encoded certificates presented by the peer, the end-entity certificate first.
+    /// Empty if the peer presented none, e.g. a client without a certificate. With `native-tls`
+    /// only the end-entity certificate is available.
+    pub peer_certificates: Vec<Vec<u8>>,
+}
+
+impl<S: Read + Write> MaybeTlsStream<S> {
+    /// Returns the details of the TLS session, `None` if the stream is not encrypted or the
+    /// handshake is still in progress.
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        match self {
+            MaybeTlsStream::Plain(_) => None,
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(_) => None,
+            MaybeTlsStream::Custom(s) => s.tls_info(),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTls(s) => Some(TlsInfo {
+                version: None,
+                cipher_suite: None,
+                alpn_protocol: s.negotiated_alpn().ok().flatten(),
+                peer_certificates: s
+                    .peer_certificate()
+                    .ok()
+                    .flatten()
+                    .and_then(|cert| cert.to_der().ok())
+                    .into_iter()
+                    .collect(),
+            }),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(_) => None,
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSsl(s) => Some(openssl_info(s.ssl())),
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(_) => None,
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::Rustls(s) => rustls_info(&s.conn),
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::RustlsServer(s) => rustls_info(&s.conn),
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+fn openssl_info(ssl: &openssl::ssl::SslRef) -> TlsInfo {
+    let version = match ssl.version_str() {
+        "TLSv1.3" => "TLSv1_3",
+        "TLSv1.2" => "TLSv1_2",
+        "TLSv1.1" => "TLSv1_1",
+        "TLSv1" => "TLSv1_0",
+        other => other,
+    };
+    // The chain of a client includes the end-entity certificate, the one of a server does not.
+    let mut peer_certificates: Vec<Vec<u8>> =
+        ssl.peer_certificate().and_then(|cert| cert.to_der().ok()).into_iter().collect();
+    for cert in ssl.peer_cert_chain().into_iter().flatten() {
+        if let Ok(der) = cert.to_der() {
+            if !peer_certificates.contains(&der) {
+                peer_certificates.push(der);
+            }
+        }
+    }
+    TlsInfo {
+        version: Some(version.to_owned()),
+        cipher_suite: ssl.current_cipher().map(|cipher| cipher.name().to_owned()),
+        alpn_protocol: ssl.selected_alpn_protocol().map(<[u8]>::to_vec),
+        peer_certificates,
+    }
+}
+
+#[cfg(feature = "__rustls-tls")]
+fn rustls_info(conn: &rustls::CommonState) -> Option<TlsInfo> {
+    if conn.is_handshaking() {
+        return None;
+    }
+    Some(TlsInfo {
+        version: conn.protocol_version().and_then(|v| v.as_str()).map(str::to_owned),
+        cipher_suite: conn
+            .negotiated_cipher_suite()
+            .and_then(|suite| suite.suite().as_str())
+            .map(str::to_owned),
+        alpn_protocol: conn.alpn_protocol().map(<[u8]>::to_vec),
+        peer_certificates: conn
+            .peer_certificates()
+            .map_or_else(Vec::new, |certs| certs.iter().map(|cert| cert.to_vec()).collect()),
+    })
 }
 
 impl<S: Read + Write + Debug> Debug for MaybeTlsStream<S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Plain(s) => f.debug_tuple("MaybeTlsStream::Plain").field(s).finish(),
+            #[cfg(unix)]
+            Self::Unix(s) => f.debug_tuple("MaybeTlsStream::Unix").field(s).finish(),
+            Self::Custom(s) => f.debug_tuple("MaybeTlsStream::Custom").field(s.get_ref()).finish(),
             #[cfg(feature = "native-tls")]
             Self::NativeTls(s) => f.debug_tuple("MaybeTlsStream::NativeTls").field(s).finish(),
+            #[cfg(feature = "native-tls")]
+            Self::NativeTlsHandshake(s) => {
+                f.debug_tuple("MaybeTlsStream::NativeTlsHandshake").field(s).finish()
+            }
+            #[cfg(feature = "openssl")]
+            Self::OpenSsl(s) => f.debug_tuple("MaybeTlsStream::OpenSsl").field(s).finish(),
+            #[cfg(feature = "openssl")]
+            Self::OpenSslHandshake(s) => {
+                f.debug_tuple("MaybeTlsStream::OpenSslHandshake").field(s).finish()
+            }
             #[cfg(feature = "__rustls-tls")]
             Self::Rustls(s) => {
-                struct RustlsStreamDebug<'a, S: Read + Write>(
-                    &'a rustls::StreamOwned<rustls::ClientConnection, S>,
-                );
-
-                impl<'a, S: Read + Write + Debug> Debug for RustlsStreamDebug<'a, S> {
-                    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                        f.debug_struct("StreamOwned")
-                            .field("conn", &self.0.conn)
-                            .field("sock", &self.0.sock)
-                            .finish()
-                    }
-                }
-
                 f.debug_tuple("MaybeTlsStream::Rustls").field(&RustlsStreamDebug(s)).finish()
             }
+            #[cfg(feature = "__rustls-tls")]
+            Self::RustlsServer(s) => {
+                f.debug_tuple("MaybeTlsStream::RustlsServer").field(&RustlsStreamDebug(s)).finish()
+            }
         }
     }
 }
 
+#[cfg(feature = "__rustls-tls")]
+struct RustlsStreamDebug<'a, C, S: Read + Write>(&'a rustls::StreamOwned<C, S>);
+
+#[cfg(feature = "__rustls-tls")]
+impl<'a, C: Debug, S: Read + Write + Debug> Debug for RustlsStreamDebug<'a, C, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamOwned")
+            .field("conn", &self.0.conn)
+            .field("sock", &self.0.sock)
+            .finish()
+    }
+}
+
 impl<S: Read + Write> Read for MaybeTlsStream<S> {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.complete_handshake()?;
         match *self {
             MaybeTlsStream::Plain(ref mut s) => s.read(buf),
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(ref mut s) => s.read(buf),
+            MaybeTlsStream::Custom(ref mut s) => s.read(buf),
             #[cfg(feature = "native-tls")]
             MaybeTlsStream::NativeTls(ref mut s) => s.read(buf),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSsl(ref mut s) => s.read(buf),
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
             #[cfg(feature = "__rustls-tls")]
             MaybeTlsStream::Rustls(ref mut s) => s.read(buf),
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::RustlsServer(ref mut s) => s.read(buf),
         }
     }
 }
 
 impl<S: Read + Write> Write for MaybeTlsStream<S> {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.complete_handshake()?;
         match *self {
             MaybeTlsStream::Plain(ref mut s) => s.write(buf),
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(ref mut s) => s.write(buf),
+            MaybeTlsStream::Custom(ref mut s) => s.write(buf),
             #[cfg(feature = "native-tls")]
             MaybeTlsStream::NativeTls(ref mut s) => s.write(buf),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSsl(ref mut s) => s.write(buf),
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
             #[cfg(feature = "__rustls-tls")]
             MaybeTlsStream::Rustls(ref mut s) => s.write(buf),
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::RustlsServer(ref mut s) => s.write(buf),
         }
     }
 
     fn flush(&mut self) -> IoResult<()> {
+        self.complete_handshake()?;
         match *self {
             MaybeTlsStream::Plain(ref mut s) => s.flush(),
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(ref mut s) => s.flush(),
+            MaybeTlsStream::Custom(ref mut s) => s.flush(),
             #[cfg(feature = "native-tls")]
             MaybeTlsStream::NativeTls(ref mut s) => s.flush(),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSsl(ref mut s) => s.flush(),
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(_) => {
+                unreachable!("Bug: TLS handshake not completed")
+            }
             #[cfg(feature = "__rustls-tls")]
             MaybeTlsStream::Rustls(ref mut s) => s.flush(),
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::RustlsServer(ref mut s) => s.flush(),
+        }
+    }
+}
+
+/// Only plain streams can be cloned, TLS streams fail with [`std::io::ErrorKind::Unsupported`].
+impl<S: Read + Write + TryClone> TryClone for MaybeTlsStream<S> {
+    fn try_clone(&self) -> IoResult<Self> {
+        match *self {
+            MaybeTlsStream::Plain(ref s) => s.try_clone().map(MaybeTlsStream::Plain),
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(ref s) => s.try_clone().map(MaybeTlsStream::Unix),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "TLS streams can't be cloned",
+            )),
         }
     }
 }
@@ -136,10 +500,27 @@ impl<S: Read + Write + NoDelay> NoDelay for MaybeTlsStream<S> {
     fn set_nodelay(&mut self, nodelay: bool) -> IoResult<()> {
         match *self {
             MaybeTlsStream::Plain(ref mut s) => s.set_nodelay(nodelay),
+            #[cfg(unix)]
+            MaybeTlsStream::Unix(ref mut s) => s.set_nodelay(nodelay),
+            MaybeTlsStream::Custom(ref mut s) => s.get_mut().set_nodelay(nodelay),
             #[cfg(feature = "native-tls")]
             MaybeTlsStream::NativeTls(ref mut s) => s.set_nodelay(nodelay),
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTlsHandshake(ref mut s) => match s.get_mut() {
+                Some(s) => s.set_nodelay(nodelay),
+                None => Ok(()),
+            },
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSsl(ref mut s) => s.set_nodelay(nodelay),
+            #[cfg(feature = "openssl")]
+            MaybeTlsStream::OpenSslHandshake(ref mut s) => match s.get_mut() {
+                Some(s) => s.set_nodelay(nodelay),
+                None => Ok(()),
+            },
             #[cfg(feature = "__rustls-tls")]
             MaybeTlsStream::Rustls(ref mut s) => s.set_nodelay(nodelay),
+            #[cfg(feature = "__rustls-tls")]
+            MaybeTlsStream::RustlsServer(ref mut s) => s.set_nodelay(nodelay),
         }
     }
 }
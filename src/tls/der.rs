@@ -0,0 +1,136 @@
+//! Just enough DER to take apart and assemble the key and certificate containers the TLS
+//! libraries don't read themselves.
+
+// `native-tls` and `rustls` each use only a part of it.
+#![cfg_attr(not(all(feature = "native-tls", feature = "__rustls-tls")), allow(dead_code))]
+
+pub const INTEGER: u8 = 0x02;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// The tag of the constructed context-specific value `[n]`.
+pub const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// Reads consecutive DER values from a buffer.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The tag of the next value, if any.
+    pub fn peek(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Read the tag and the contents of the next value. Fails on truncated values and on the
+    /// indefinite lengths of BER.
+    pub fn any(&mut self) -> Option<(u8, &'a [u8])> {
+        let (&tag, rest) = self.data.split_first()?;
+        let (&first, mut rest) = rest.split_first()?;
+        let len = if first < 0x80 {
+            usize::from(first)
+        } else {
+            let count = usize::from(first & 0x7f);
+            if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+                return None;
+            }
+            let (bytes, tail) = rest.split_at(count);
+            rest = tail;
+            bytes.iter().fold(0, |len, b| len << 8 | usize::from(*b))
+        };
+        if rest.len() < len {
+            return None;
+        }
+        let (contents, rest) = rest.split_at(len);
+        self.data = rest;
+        Some((tag, contents))
+    }
+
+    /// Read the contents of the next value, which must have the tag.
+    pub fn read(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.any()? {
+            (found, contents) if found == tag => Some(contents),
+            _ => None,
+        }
+    }
+
+    /// Read the contents of the next value if it has the tag.
+    pub fn optional(&mut self, tag: u8) -> Option<&'a [u8]> {
+        if self.peek() == Some(tag) {
+            self.read(tag)
+        } else {
+            None
+        }
+    }
+
+    /// Read a non-negative integer fitting into a `u32`.
+    pub fn u32(&mut self) -> Option<u32> {
+        let contents = self.read(INTEGER)?;
+        let contents = match contents {
+            [0, rest @ ..] if !rest.is_empty() => rest,
+            [first, ..] if first & 0x80 == 0 => contents,
+            _ => return None,
+        };
+        if contents.len() > 4 {
+            return None;
+        }
+        Some(contents.iter().fold(0, |n, b| n << 8 | u32::from(*b)))
+    }
+}
+
+/// Encode a value with its tag and length.
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    if contents.len() < 0x80 {
+        der.push(contents.len() as u8);
+    } else {
+        let len = contents.len().to_be_bytes();
+        let zeros = len.iter().take_while(|b| **b == 0).count();
+        der.push(0x80 | (len.len() - zeros) as u8);
+        der.extend_from_slice(&len[zeros..]);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, Reader, INTEGER, OCTET_STRING, SEQUENCE};
+
+    #[test]
+    fn round_trip() {
+        let long = vec![7; 300];
+        let der = encode(SEQUENCE, &[encode(INTEGER, &[1]), encode(OCTET_STRING, &long)].concat());
+        assert_eq!(der[..4], [SEQUENCE, 0x82, 0x01, 0x33]);
+
+        let mut reader = Reader::new(&der);
+        let mut sequence = Reader::new(reader.read(SEQUENCE).unwrap());
+        assert!(reader.is_empty());
+        assert_eq!(sequence.u32(), Some(1));
+        assert_eq!(sequence.optional(INTEGER), None);
+        assert_eq!(sequence.read(OCTET_STRING), Some(&long[..]));
+        assert_eq!(sequence.any(), None);
+    }
+
+    #[test]
+    fn malformed() {
+        // Truncated, indefinite length and negative integer.
+        assert_eq!(Reader::new(&[OCTET_STRING, 2, 0]).any(), None);
+        assert_eq!(Reader::new(&[SEQUENCE, 0x80, 0, 0]).any(), None);
+        assert_eq!(Reader::new(&[INTEGER, 1, 0x80]).u32(), None);
+    }
+}
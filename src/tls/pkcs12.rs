@@ -0,0 +1,475 @@
+//! Decoding PKCS#12 archives (RFC 7292) into a certificate chain and a private key for
+//! `rustls`, which doesn't read them itself.
+//!
+//! Supports the password-based encryption of current archives (PBES2 with PBKDF2 and AES or
+//! 3DES) and of older ones (3DES and RC2 with the PKCS#12 key derivation), and MACs with SHA-1
+//! or SHA-2.
+
+mod cipher;
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use self::cipher::{decrypt_cbc, Aes, BlockDecrypt, Rc2, TripleDes};
+use super::der::{self, Reader};
+
+const OID_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+const OID_ENCRYPTED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x06];
+const OID_KEY_BAG: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x01];
+const OID_SHROUDED_KEY_BAG: &[u8] =
+    &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x02];
+const OID_CERT_BAG: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x03];
+const OID_X509_CERTIFICATE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x16, 0x01];
+const OID_LOCAL_KEY_ID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x15];
+
+const OID_PBE_SHA1_3DES: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x03];
+const OID_PBE_SHA1_RC2_128: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x05];
+const OID_PBE_SHA1_RC2_40: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x01, 0x06];
+const OID_PBES2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+const OID_PBKDF2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+const OID_AES_128_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x02];
+const OID_AES_192_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x16];
+const OID_AES_256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+const OID_DES_EDE3_CBC: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x03, 0x07];
+
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+const OID_HMAC_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x07];
+const OID_HMAC_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+const OID_HMAC_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0a];
+const OID_HMAC_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0b];
+
+/// The key derivations ID of the PKCS#12 KDF (RFC 7292, appendix B.3).
+const KDF_KEY: u8 = 1;
+const KDF_IV: u8 = 2;
+const KDF_MAC: u8 = 3;
+
+/// The contents of a PKCS#12 archive: the DER-encoded certificates, the one belonging to the
+/// key first, and the PKCS#8-encoded private key.
+#[derive(Debug)]
+pub struct Contents {
+    pub chain: Vec<Vec<u8>>,
+    pub key: Vec<u8>,
+}
+
+/// Decode and decrypt a DER-encoded PKCS#12 archive. The error describes why it failed.
+pub fn decode(archive: &[u8], password: &str) -> Result<Contents, String> {
+    let malformed = || "malformed PKCS#12 archive".to_owned();
+    let mut pfx =
+        Reader::new(archive).read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    if pfx.u32() != Some(3) {
+        return Err("unsupported PKCS#12 version".into());
+    }
+    let auth_safe = data_content(pfx.read(der::SEQUENCE).ok_or_else(malformed)?)
+        .ok_or_else(|| "malformed or public-key protected PKCS#12 archive".to_owned())?;
+
+    // Before OpenSSL 1.1, empty passwords were encoded without the terminating zero.
+    let mut passwords = vec![Password::new(password)];
+    if password.is_empty() {
+        passwords.push(Password { utf8: Vec::new(), bmp: Vec::new() });
+    }
+    let password = match pfx.optional(der::SEQUENCE) {
+        Some(mac_data) => passwords
+            .into_iter()
+            .find(|password| verify_mac(mac_data, auth_safe, password).unwrap_or(false))
+            .ok_or_else(|| "wrong password or corrupted PKCS#12 archive".to_owned())?,
+        None => passwords.remove(0),
+    };
+
+    let mut bags = Vec::new();
+    let mut infos = Reader::new(Reader::new(auth_safe).read(der::SEQUENCE).ok_or_else(malformed)?);
+    while !infos.is_empty() {
+        let info = infos.read(der::SEQUENCE).ok_or_else(malformed)?;
+        let safe_contents = match data_content(info) {
+            Some(data) => data.to_vec(),
+            None => decrypt_data(info, &password)?,
+        };
+        read_bags(&safe_contents, &password, &mut bags)?;
+    }
+
+    let mut keys = bags.iter().filter_map(|bag| match bag {
+        Bag::Key { key, local_key_id } => Some((key, local_key_id)),
+        Bag::Certificate { .. } => None,
+    });
+    let (key, key_id) = keys.next().ok_or_else(|| "no private key found".to_owned())?;
+    if keys.next().is_some() {
+        return Err("more than one private key found".into());
+    }
+    let mut chain: Vec<(Vec<u8>, bool)> = bags
+        .iter()
+        .filter_map(|bag| match bag {
+            Bag::Certificate { certificate, local_key_id } => {
+                Some((certificate.clone(), key_id.is_some() && local_key_id == key_id))
+            }
+            Bag::Key { .. } => None,
+        })
+        .collect();
+    if chain.is_empty() {
+        return Err("no certificate found".into());
+    }
+    // The certificate of the key is the one with the same local key ID, the first otherwise.
+    if let Some(position) = chain.iter().position(|(_, own)| *own) {
+        let own = chain.remove(position);
+        chain.insert(0, own);
+    }
+    Ok(Contents { chain: chain.into_iter().map(|(cert, _)| cert).collect(), key: key.clone() })
+}
+
+/// The password in the encodings of PBES2 (UTF-8) and the PKCS#12 KDF (zero-terminated
+/// UTF-16BE).
+struct Password {
+    utf8: Vec<u8>,
+    bmp: Vec<u8>,
+}
+
+impl Password {
+    fn new(password: &str) -> Self {
+        let mut bmp: Vec<u8> = password.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        bmp.extend_from_slice(&[0, 0]);
+        Password { utf8: password.as_bytes().to_vec(), bmp }
+    }
+}
+
+enum Bag {
+    Key { key: Vec<u8>, local_key_id: Option<Vec<u8>> },
+    Certificate { certificate: Vec<u8>, local_key_id: Option<Vec<u8>> },
+}
+
+/// The contents of a `ContentInfo` of type `data`, `None` for other types.
+fn data_content(info: &[u8]) -> Option<&[u8]> {
+    let mut info = Reader::new(info);
+    if info.read(der::OID)? != OID_DATA {
+        return None;
+    }
+    Reader::new(info.read(der::context(0))?).read(der::OCTET_STRING)
+}
+
+/// Decrypt the contents of a `ContentInfo` of type `encryptedData`.
+fn decrypt_data(info: &[u8], password: &Password) -> Result<Vec<u8>, String> {
+    let malformed = || "malformed encrypted PKCS#12 contents".to_owned();
+    let mut info = Reader::new(info);
+    if info.read(der::OID) != Some(OID_ENCRYPTED_DATA) {
+        return Err("unsupported PKCS#12 contents".into());
+    }
+    let mut encrypted_data = Reader::new(info.read(der::context(0)).ok_or_else(malformed)?)
+        .read(der::SEQUENCE)
+        .map(Reader::new)
+        .ok_or_else(malformed)?;
+    encrypted_data.u32().ok_or_else(malformed)?;
+    let mut content_info =
+        encrypted_data.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    if content_info.read(der::OID) != Some(OID_DATA) {
+        return Err("unsupported PKCS#12 contents".into());
+    }
+    let algorithm = content_info.read(der::SEQUENCE).ok_or_else(malformed)?;
+    // `[0] IMPLICIT OCTET STRING`, primitive.
+    let encrypted = content_info.read(0x80).ok_or_else(malformed)?;
+    decrypt(algorithm, encrypted, password)
+}
+
+fn read_bags(safe_contents: &[u8], password: &Password, bags: &mut Vec<Bag>) -> Result<(), String> {
+    let malformed = || "malformed PKCS#12 bag".to_owned();
+    let mut safe_contents =
+        Reader::new(safe_contents).read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    while !safe_contents.is_empty() {
+        let mut bag = safe_contents.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+        let id = bag.read(der::OID).ok_or_else(malformed)?;
+        let value = bag.read(der::context(0)).ok_or_else(malformed)?;
+        let local_key_id = bag.optional(der::SET).and_then(local_key_id);
+        let value = Reader::new(value).read(der::SEQUENCE).ok_or_else(malformed)?;
+        if id == OID_KEY_BAG {
+            bags.push(Bag::Key { key: der::encode(der::SEQUENCE, value), local_key_id });
+        } else if id == OID_SHROUDED_KEY_BAG {
+            let mut value = Reader::new(value);
+            let algorithm = value.read(der::SEQUENCE).ok_or_else(malformed)?;
+            let encrypted = value.read(der::OCTET_STRING).ok_or_else(malformed)?;
+            bags.push(Bag::Key { key: decrypt(algorithm, encrypted, password)?, local_key_id });
+        } else if id == OID_CERT_BAG {
+            let mut value = Reader::new(value);
+            // Other certificate types, e.g. SDSI, are ignored.
+            if value.read(der::OID) == Some(OID_X509_CERTIFICATE) {
+                let certificate = Reader::new(value.read(der::context(0)).ok_or_else(malformed)?)
+                    .read(der::OCTET_STRING)
+                    .ok_or_else(malformed)?;
+                bags.push(Bag::Certificate { certificate: certificate.to_vec(), local_key_id });
+            }
+        }
+        // Other bags, e.g. CRLs, are ignored.
+    }
+    Ok(())
+}
+
+/// The value of the `localKeyId` attribute in the attributes of a bag.
+fn local_key_id(attributes: &[u8]) -> Option<Vec<u8>> {
+    let mut attributes = Reader::new(attributes);
+    while !attributes.is_empty() {
+        let mut attribute = Reader::new(attributes.read(der::SEQUENCE)?);
+        if attribute.read(der::OID)? == OID_LOCAL_KEY_ID {
+            return Reader::new(attribute.read(der::SET)?)
+                .read(der::OCTET_STRING)
+                .map(<[u8]>::to_vec);
+        }
+    }
+    None
+}
+
+/// Verify the `MacData` of the archive.
+fn verify_mac(mac_data: &[u8], auth_safe: &[u8], password: &Password) -> Option<bool> {
+    let mut mac_data = Reader::new(mac_data);
+    let mut digest_info = Reader::new(mac_data.read(der::SEQUENCE)?);
+    let mut algorithm = Reader::new(digest_info.read(der::SEQUENCE)?);
+    let hash = Hash::from_digest_oid(algorithm.read(der::OID)?)?;
+    let expected = digest_info.read(der::OCTET_STRING)?;
+    let salt = mac_data.read(der::OCTET_STRING)?;
+    let iterations = if mac_data.is_empty() { 1 } else { mac_data.u32()? };
+    let key = pkcs12_kdf(hash, &password.bmp, salt, KDF_MAC, iterations, hash.output_len());
+    Some(constant_time_eq(&hmac(hash, &key, &[auth_safe]), expected))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Decrypt data with a password-based encryption algorithm.
+fn decrypt(algorithm: &[u8], encrypted: &[u8], password: &Password) -> Result<Vec<u8>, String> {
+    let malformed = || "malformed PKCS#12 encryption parameters".to_owned();
+    let wrong_password = || "wrong password or corrupted PKCS#12 archive".to_owned();
+    let mut algorithm = Reader::new(algorithm);
+    let oid = algorithm.read(der::OID).ok_or_else(malformed)?;
+    let mut params = algorithm.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    if oid == OID_PBES2 {
+        return decrypt_pbes2(params, encrypted, password);
+    }
+
+    let salt = params.read(der::OCTET_STRING).ok_or_else(malformed)?;
+    let iterations = params.u32().ok_or_else(malformed)?;
+    let derive = |id, len| pkcs12_kdf(Hash::Sha1, &password.bmp, salt, id, iterations, len);
+    let plain = if oid == OID_PBE_SHA1_3DES {
+        let cipher = TripleDes::new(&derive(KDF_KEY, 24)).ok_or_else(malformed)?;
+        decrypt_cbc(&cipher, &derive(KDF_IV, TripleDes::BLOCK_LEN), encrypted)
+    } else if oid == OID_PBE_SHA1_RC2_128 || oid == OID_PBE_SHA1_RC2_40 {
+        let len = if oid == OID_PBE_SHA1_RC2_40 { 5 } else { 16 };
+        let cipher = Rc2::new(&derive(KDF_KEY, len), 8 * len).ok_or_else(malformed)?;
+        decrypt_cbc(&cipher, &derive(KDF_IV, Rc2::BLOCK_LEN), encrypted)
+    } else {
+        return Err("unsupported PKCS#12 encryption algorithm".into());
+    };
+    plain.ok_or_else(wrong_password)
+}
+
+/// Decrypt data encrypted with PBES2 (RFC 8018), with PBKDF2 as key derivation function.
+fn decrypt_pbes2(
+    mut params: Reader<'_>,
+    encrypted: &[u8],
+    password: &Password,
+) -> Result<Vec<u8>, String> {
+    let malformed = || "malformed PKCS#12 encryption parameters".to_owned();
+    let unsupported = || "unsupported PKCS#12 encryption algorithm".to_owned();
+    let mut kdf = params.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    let mut scheme = params.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    if kdf.read(der::OID) != Some(OID_PBKDF2) {
+        return Err(unsupported());
+    }
+    let mut kdf_params = kdf.read(der::SEQUENCE).map(Reader::new).ok_or_else(malformed)?;
+    let salt = kdf_params.read(der::OCTET_STRING).ok_or_else(malformed)?;
+    let iterations = kdf_params.u32().ok_or_else(malformed)?;
+    // The optional key length is implied by the cipher.
+    kdf_params.optional(der::INTEGER);
+    let prf = match kdf_params.optional(der::SEQUENCE) {
+        Some(prf) => Hash::from_hmac_oid(Reader::new(prf).read(der::OID).ok_or_else(malformed)?)
+            .ok_or_else(unsupported)?,
+        None => Hash::Sha1,
+    };
+
+    let oid = scheme.read(der::OID).ok_or_else(malformed)?;
+    let iv = scheme.read(der::OCTET_STRING).ok_or_else(malformed)?;
+    let key_len = match oid {
+        OID_AES_128_CBC => 16,
+        OID_AES_192_CBC => 24,
+        OID_AES_256_CBC => 32,
+        OID_DES_EDE3_CBC => 24,
+        _ => return Err(unsupported()),
+    };
+    let key = pbkdf2(prf, &password.utf8, salt, iterations, key_len);
+    let plain = if oid == OID_DES_EDE3_CBC {
+        decrypt_cbc(&TripleDes::new(&key).ok_or_else(malformed)?, iv, encrypted)
+    } else {
+        decrypt_cbc(&Aes::new(&key).ok_or_else(malformed)?, iv, encrypted)
+    };
+    plain.ok_or_else(|| "wrong password or corrupted PKCS#12 archive".to_owned())
+}
+
+/// The hash functions used by PKCS#12 MACs and key derivations.
+#[derive(Debug, Clone, Copy)]
+enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Hash {
+    fn from_digest_oid(oid: &[u8]) -> Option<Self> {
+        match oid {
+            OID_SHA1 => Some(Hash::Sha1),
+            OID_SHA256 => Some(Hash::Sha256),
+            OID_SHA384 => Some(Hash::Sha384),
+            OID_SHA512 => Some(Hash::Sha512),
+            _ => None,
+        }
+    }
+
+    fn from_hmac_oid(oid: &[u8]) -> Option<Self> {
+        match oid {
+            OID_HMAC_SHA1 => Some(Hash::Sha1),
+            OID_HMAC_SHA256 => Some(Hash::Sha256),
+            OID_HMAC_SHA384 => Some(Hash::Sha384),
+            OID_HMAC_SHA512 => Some(Hash::Sha512),
+            _ => None,
+        }
+    }
+
+    fn output_len(self) -> usize {
+        match self {
+            Hash::Sha1 => 20,
+            Hash::Sha256 => 32,
+            Hash::Sha384 => 48,
+            Hash::Sha512 => 64,
+        }
+    }
+
+    fn block_len(self) -> usize {
+        match self {
+            Hash::Sha1 | Hash::Sha256 => 64,
+            Hash::Sha384 | Hash::Sha512 => 128,
+        }
+    }
+
+    /// The hash of the concatenated parts.
+    fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize().to_vec()
+        }
+        match self {
+            Hash::Sha1 => digest::<Sha1>(parts),
+            Hash::Sha256 => digest::<Sha256>(parts),
+            Hash::Sha384 => digest::<Sha384>(parts),
+            Hash::Sha512 => digest::<Sha512>(parts),
+        }
+    }
+}
+
+/// HMAC (RFC 2104) of the concatenated parts.
+fn hmac(hash: Hash, key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut key = if key.len() > hash.block_len() { hash.digest(&[key]) } else { key.to_vec() };
+    key.resize(hash.block_len(), 0);
+    let inner_key: Vec<u8> = key.iter().map(|b| b ^ 0x36).collect();
+    let outer_key: Vec<u8> = key.iter().map(|b| b ^ 0x5c).collect();
+    let mut inner = vec![&inner_key[..]];
+    inner.extend_from_slice(parts);
+    hash.digest(&[&outer_key, &hash.digest(&inner)])
+}
+
+/// PBKDF2 (RFC 8018) with HMAC as pseudorandom function.
+fn pbkdf2(prf: Hash, password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut key = Vec::with_capacity(len);
+    let mut index = 1u32;
+    while key.len() < len {
+        let mut u = hmac(prf, password, &[salt, &index.to_be_bytes()]);
+        let mut block = u.clone();
+        for _ in 1..iterations {
+            u = hmac(prf, password, &[&u]);
+            block.iter_mut().zip(&u).for_each(|(b, u)| *b ^= u);
+        }
+        key.extend_from_slice(&block);
+        index += 1;
+    }
+    key.truncate(len);
+    key
+}
+
+/// The key derivation function of PKCS#12 (RFC 7292, appendix B.2).
+fn pkcs12_kdf(
+    hash: Hash,
+    password: &[u8],
+    salt: &[u8],
+    id: u8,
+    iterations: u32,
+    len: usize,
+) -> Vec<u8> {
+    let v = hash.block_len();
+    let fill = |data: &[u8]| -> Vec<u8> {
+        let len = (data.len() + v - 1) / v * v;
+        data.iter().cycle().take(len).copied().collect()
+    };
+    let diversifier = vec![id; v];
+    let mut input = fill(salt);
+    input.extend(fill(password));
+
+    let mut key = Vec::with_capacity(len);
+    loop {
+        let mut a = hash.digest(&[&diversifier, &input]);
+        for _ in 1..iterations {
+            a = hash.digest(&[&a]);
+        }
+        key.extend_from_slice(&a);
+        if key.len() >= len {
+            key.truncate(len);
+            return key;
+        }
+        // Add the hash repeated to a block, plus one, to each block of the input.
+        let b: Vec<u8> = a.iter().cycle().take(v).copied().collect();
+        for block in input.chunks_mut(v) {
+            let mut carry = 1u16;
+            for (i, b) in block.iter_mut().zip(&b).rev() {
+                let sum = u16::from(*i) + u16::from(*b) + carry;
+                *i = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hmac, pbkdf2, pkcs12_kdf, Hash};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn hmac_sha256() {
+        // RFC 4231, test case 2.
+        let mac = hmac(Hash::Sha256, b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(mac, hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"));
+    }
+
+    #[test]
+    fn pbkdf2_vectors() {
+        // RFC 6070 and RFC 7914.
+        let key = pbkdf2(Hash::Sha1, b"password", b"salt", 2, 20);
+        assert_eq!(key, hex("ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957"));
+        let key = pbkdf2(Hash::Sha256, b"passwd", b"salt", 1, 64);
+        assert_eq!(
+            key,
+            hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                 49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783")
+        );
+    }
+
+    #[test]
+    fn pkcs12_kdf_vectors() {
+        // Password "smeg", as encoded by PKCS#12.
+        let password = [0, b's', 0, b'm', 0, b'e', 0, b'g', 0, 0];
+        let salt = hex("0a58cf64530d823f");
+        let key = pkcs12_kdf(Hash::Sha1, &password, &salt, 1, 1, 24);
+        assert_eq!(key, hex("8aaae6297b6cb04642ab5b077851284eb7128f1a2a7fbca3"));
+        let iv = pkcs12_kdf(Hash::Sha1, &password, &salt, 2, 1, 8);
+        assert_eq!(iv, hex("79993dfe048d3b76"));
+    }
+}
@@ -0,0 +1,318 @@
+//! Presenting client certificates (mutual TLS) with the connectors created from `TlsOptions`.
+
+#![cfg(all(feature = "handshake", any(feature = "openssl", feature = "__rustls-tls")))]
+
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{spawn, JoinHandle},
+};
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+use tungstenite::{accept_tls, client_tls_with_config, Acceptor, Connector, Message};
+
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+/// A certificate issued by [`Ca`] with its key.
+struct Issued {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, "Test CA");
+        Ca { cert: params.self_signed(&key).unwrap(), key }
+    }
+
+    fn issue(&self, name: &str) -> Issued {
+        self.issue_for(name, KeyPair::generate().unwrap())
+    }
+
+    fn issue_for(&self, name: &str, key: KeyPair) -> Issued {
+        // OpenSSL takes certificates with the issuer's name for self-signed ones.
+        let mut params = CertificateParams::new(vec![name.to_owned()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        Issued { cert, key }
+    }
+}
+
+/// Accepts one connection, checks the client certificate it presented and echoes one message.
+fn serve(acceptor: Acceptor, client: Option<Vec<u8>>) -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        match accept_tls(stream, acceptor) {
+            Ok(mut ws) => {
+                let info = ws.get_ref().tls_info().unwrap();
+                assert_eq!(info.peer_certificates.first(), client.as_ref());
+                let msg = ws.read().unwrap();
+                ws.send(msg).unwrap();
+                while ws.read().is_ok() {}
+            }
+            Err(e) => assert!(client.is_none(), "{:?}", e),
+        }
+    });
+    (addr, server)
+}
+
+/// A PKCS#12 archive of the client certificate, its key and the CA certificate. OpenSSL's
+/// defaults are used unless `legacy` asks for the 3DES and SHA-1 of older archives.
+#[cfg(feature = "openssl")]
+fn pkcs12(ca: &Ca, client: &Issued, password: &str, legacy: bool) -> Vec<u8> {
+    use openssl::{
+        hash::MessageDigest, nid::Nid, pkcs12::Pkcs12, pkey::PKey, stack::Stack, x509::X509,
+    };
+
+    let mut chain = Stack::new().unwrap();
+    chain.push(X509::from_der(ca.cert.der()).unwrap()).unwrap();
+    let mut builder = Pkcs12::builder();
+    builder
+        .name("client")
+        .pkey(&PKey::private_key_from_der(&client.key.serialize_der()).unwrap())
+        .cert(&X509::from_der(client.cert.der()).unwrap())
+        .ca(chain);
+    if legacy {
+        builder
+            .key_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .cert_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .mac_md(MessageDigest::sha1());
+    }
+    builder.build2(password).unwrap().to_der().unwrap()
+}
+
+fn echo(addr: SocketAddr, connector: Connector) {
+    let stream = TcpStream::connect(addr).unwrap();
+    let (mut ws, _) =
+        client_tls_with_config("wss://localhost/socket", stream, None, Some(connector)).unwrap();
+    ws.send(Message::text("Hello with a certificate")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello with a certificate"));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+}
+
+#[cfg(feature = "__rustls-tls")]
+mod with_rustls {
+    use std::{net::TcpStream, sync::Arc};
+
+    use rustls::{
+        crypto::ring::default_provider,
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        server::WebPkiClientVerifier,
+        RootCertStore, ServerConfig,
+    };
+    use tungstenite::{
+        client_tls_with_config, error::TlsError, Acceptor, Connector, Error, Identity, TlsOptions,
+    };
+
+    use super::{echo, serve, Ca};
+
+    /// Requires client certificates issued by `ca`.
+    fn acceptor(ca: &Ca) -> Acceptor {
+        let provider = Arc::new(default_provider());
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone()).unwrap();
+        let verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .unwrap();
+        let server = ca.issue("localhost");
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server.key.serialize_der()));
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(vec![server.cert.der().clone()], key)
+            .unwrap();
+        Acceptor::Rustls(Arc::new(config))
+    }
+
+    fn options(identity: Option<Identity>) -> TlsOptions {
+        // The server certificate is issued by the test CA, which isn't a trusted root.
+        TlsOptions {
+            verify_roots: false,
+            verify: Some(Arc::new(|_, _, _| Ok(()))),
+            identity,
+            ..TlsOptions::default()
+        }
+    }
+
+    #[test]
+    fn pem_identity() {
+        let ca = Ca::new();
+        let client = ca.issue("client");
+        let chain = client.cert.pem() + &ca.cert.pem();
+        let identity = Identity::from_pem(chain.as_bytes(), client.key.serialize_pem().as_bytes());
+
+        let (addr, server) = serve(acceptor(&ca), Some(client.cert.der().to_vec()));
+        echo(addr, Connector::rustls_with_options(&options(Some(identity))).unwrap());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn missing_identity() {
+        let ca = Ca::new();
+        let (addr, server) = serve(acceptor(&ca), None);
+        let connector = Connector::rustls_with_options(&options(None)).unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+        // TLS 1.3 clients learn of the rejection when reading the first server message.
+        if let Ok((mut ws, _)) =
+            client_tls_with_config("wss://localhost/socket", stream, None, Some(connector))
+        {
+            ws.read().unwrap_err();
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_identities() {
+        let ca = Ca::new();
+        let key = ca.key.serialize_pem();
+        let invalid = [
+            Identity::from_pem(b"", key.as_bytes()),
+            Identity::from_pem(ca.cert.pem().as_bytes(), b""),
+        ];
+        for identity in invalid {
+            match Connector::<TcpStream>::rustls_with_options(&options(Some(identity))) {
+                Err(Error::Tls(TlsError::InvalidIdentity(_))) => {}
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+        }
+
+        let invalid = [Identity::from_pkcs12(b"", ""), Identity::from_pkcs12(&[0x30, 0], "")];
+        for identity in invalid {
+            match Connector::<TcpStream>::rustls_with_options(&options(Some(identity))) {
+                Err(Error::Tls(TlsError::InvalidIdentity(_))) => {}
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn pkcs12_identity() {
+        for legacy in [false, true] {
+            let ca = Ca::new();
+            let client = ca.issue("client");
+            let archive = super::pkcs12(&ca, &client, "secret", legacy);
+            let identity = Identity::from_pkcs12(&archive, "secret");
+
+            let (addr, server) = serve(acceptor(&ca), Some(client.cert.der().to_vec()));
+            echo(addr, Connector::rustls_with_options(&options(Some(identity))).unwrap());
+            server.join().unwrap();
+
+            let identity = Identity::from_pkcs12(&archive, "wrong");
+            match Connector::<TcpStream>::rustls_with_options(&options(Some(identity))) {
+                Err(Error::Tls(TlsError::InvalidIdentity(_))) => {}
+                other => panic!("unexpected result: {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+mod with_openssl {
+    use std::net::TcpStream;
+
+    use openssl::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod, SslVerifyMode},
+        x509::X509,
+    };
+    use tungstenite::{error::TlsError, Acceptor, Connector, Error, Identity, TlsOptions};
+
+    use super::{echo, pkcs12, serve, Ca};
+
+    /// Requires client certificates issued by `ca`.
+    fn acceptor(ca: &Ca) -> Acceptor {
+        let server = ca.issue("localhost");
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor.set_certificate(&X509::from_der(server.cert.der()).unwrap()).unwrap();
+        acceptor
+            .set_private_key(&PKey::private_key_from_der(&server.key.serialize_der()).unwrap())
+            .unwrap();
+        acceptor.cert_store_mut().add_cert(X509::from_der(ca.cert.der()).unwrap()).unwrap();
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        Acceptor::OpenSsl(acceptor.build())
+    }
+
+    fn options(ca: &Ca, identity: Identity) -> TlsOptions {
+        TlsOptions {
+            root_certificates: vec![ca.cert.der().to_vec()],
+            identity: Some(identity),
+            ..TlsOptions::default()
+        }
+    }
+
+    #[test]
+    fn pkcs12_identity() {
+        let ca = Ca::new();
+        let client = ca.issue("client");
+        let identity = Identity::from_pkcs12(&pkcs12(&ca, &client, "secret", false), "secret");
+
+        let (addr, server) = serve(acceptor(&ca), Some(client.cert.der().to_vec()));
+        echo(addr, Connector::openssl_with_options(&options(&ca, identity)).unwrap());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn pem_identity() {
+        let ca = Ca::new();
+        let client = ca.issue("client");
+        let chain = client.cert.pem() + &ca.cert.pem();
+        let identity = Identity::from_pem(chain.as_bytes(), client.key.serialize_pem().as_bytes());
+
+        let (addr, server) = serve(acceptor(&ca), Some(client.cert.der().to_vec()));
+        echo(addr, Connector::openssl_with_options(&options(&ca, identity)).unwrap());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn wrong_password() {
+        let ca = Ca::new();
+        let client = ca.issue("client");
+        let identity = Identity::from_pkcs12(&pkcs12(&ca, &client, "secret", false), "wrong");
+        match Connector::<TcpStream>::openssl_with_options(&options(&ca, identity)) {
+            Err(Error::Tls(TlsError::OpenSsl(_))) => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// `native-tls` takes PEM keys in the PKCS#1 and SEC1 formats as well as PKCS#8.
+    #[test]
+    #[cfg(feature = "native-tls")]
+    fn native_tls_pem_keys() {
+        use openssl::{
+            ec::{EcGroup, EcKey},
+            nid::Nid,
+            rsa::Rsa,
+        };
+        use rcgen::KeyPair;
+
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        let keys = [
+            (rsa.rsa().unwrap().private_key_to_pem().unwrap(), rsa),
+            (ec.private_key_to_pem().unwrap(), PKey::from_ec_key(ec).unwrap()),
+        ];
+        for (pem, key) in keys {
+            let pkcs8 = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+            let ca = Ca::new();
+            let client = ca.issue_for("client", KeyPair::from_pem(&pkcs8).unwrap());
+            let chain = client.cert.pem() + &ca.cert.pem();
+            let identity = Identity::from_pem(chain.as_bytes(), &pem);
+
+            let (addr, server) = serve(acceptor(&ca), Some(client.cert.der().to_vec()));
+            echo(addr, Connector::native_tls_with_options(&options(&ca, identity)).unwrap());
+            server.join().unwrap();
+        }
+    }
+}
@@ -1,7 +1,10 @@
 //! Verifies that the server returns a `ConnectionClosed` error when the connection
 //! is closed from the server's point of view and drop the underlying tcp socket.
 
-#![cfg(all(any(feature = "native-tls", feature = "__rustls-tls"), feature = "handshake"))]
+#![cfg(all(
+    any(feature = "native-tls", feature = "openssl", feature = "__rustls-tls"),
+    feature = "handshake"
+))]
 
 use std::{
     net::{TcpListener, TcpStream},
@@ -0,0 +1,120 @@
+//! Connecting with a TLS library not supported by the crate.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread::spawn,
+};
+
+use tungstenite::{
+    accept,
+    client::{connect_with_options, ConnectOptions},
+    client_tls_with_config,
+    stream::{CustomTlsStream, MaybeTlsStream, Mode, TlsInfo},
+    Connector, Message,
+};
+
+/// A stand-in for a TLS library, scrambling the bytes on the wire.
+#[derive(Debug)]
+struct Scrambled<S>(S);
+
+impl<S: Read> Read for Scrambled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        buf[..n].iter_mut().for_each(|b| *b ^= 0x5a);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Scrambled<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let scrambled: Vec<u8> = buf.iter().map(|b| b ^ 0x5a).collect();
+        self.0.write(&scrambled)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<S: Read + Write> CustomTlsStream<S> for Scrambled<S> {
+    fn get_ref(&self) -> &S {
+        &self.0
+    }
+
+    fn get_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+
+    fn tls_info(&self) -> Option<TlsInfo> {
+        let mut info = TlsInfo::default();
+        info.version = Some("scrambled".into());
+        Some(info)
+    }
+}
+
+fn echo_server() -> (u16, std::thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        let mut ws = accept(Scrambled(stream)).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+    });
+    (port, server)
+}
+
+#[test]
+fn connect_with_custom_tls() {
+    let (port, server) = echo_server();
+    let domains = Arc::new(Mutex::new(Vec::new()));
+    let seen = domains.clone();
+    let options = ConnectOptions {
+        tls_connector: Some(Arc::new(move |stream: TcpStream, domain: &str, mode| {
+            seen.lock().unwrap().push(domain.to_owned());
+            Ok(match mode {
+                Mode::Plain => MaybeTlsStream::Plain(stream),
+                Mode::Tls => MaybeTlsStream::Custom(Box::new(Scrambled(stream))),
+            })
+        })),
+        server_name: Some("echo.test".into()),
+        ..ConnectOptions::default()
+    };
+
+    let url = format!("wss://127.0.0.1:{port}/socket");
+    let (mut ws, _) = connect_with_options(url, options).unwrap();
+    assert!(matches!(ws.get_ref(), MaybeTlsStream::Custom(_)));
+    assert_eq!(ws.get_ref().tls_info().unwrap().version.as_deref(), Some("scrambled"));
+
+    ws.send(Message::text("Hello")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    server.join().unwrap();
+
+    assert_eq!(*domains.lock().unwrap(), ["echo.test"]);
+}
+
+#[test]
+fn custom_connector() {
+    let (port, server) = echo_server();
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let connector = Connector::Custom(Box::new(|stream, domain: &str, mode| {
+        assert_eq!(domain, "localhost");
+        assert!(matches!(mode, Mode::Tls));
+        Ok(MaybeTlsStream::Custom(Box::new(Scrambled(stream))))
+    }));
+    let url = format!("wss://localhost:{port}/socket");
+    let (mut ws, _) = client_tls_with_config(url, stream, None, Some(connector)).unwrap();
+
+    ws.send(Message::text("Hello")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    server.join().unwrap();
+}
@@ -0,0 +1,113 @@
+//! Fanning out messages to WebSocket subscribers with `hub::Hub`.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    net::TcpListener,
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
+};
+
+use tungstenite::{
+    accept, accept_with_config, connect,
+    hub::{Hub, HubConfig, SlowConsumerPolicy, Subscription},
+    protocol::{frame::coding::CloseCode, WebSocketConfig},
+    Message,
+};
+
+/// Accept one connection and forward the subscription to it.
+fn serve(listener: &TcpListener, subscription: Subscription) -> JoinHandle<()> {
+    let listener = listener.try_clone().unwrap();
+    spawn(move || {
+        let mut socket = accept(listener.accept().unwrap().0).unwrap();
+        subscription.forward(&mut socket).unwrap();
+        while socket.read().is_ok() {}
+    })
+}
+
+#[test]
+fn fan_out_and_slow_consumer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}/feed", listener.local_addr().unwrap());
+    let hub = Hub::new(HubConfig { queue_capacity: 4, policy: SlowConsumerPolicy::Disconnect });
+
+    let fast = hub.subscribe();
+    fast.subscribe_to("prices");
+    let fast_server = serve(&listener, fast);
+    let (mut fast_client, _) = connect(&url).unwrap();
+
+    // Subscribed, but not served before the queue overflows.
+    let slow = hub.subscribe();
+    slow.subscribe_to("prices");
+
+    for i in 0..4 {
+        hub.publish("prices", Message::text(i.to_string()));
+        assert_eq!(fast_client.read().unwrap(), Message::text(i.to_string()));
+    }
+    assert_eq!(hub.publish("prices", Message::text("4")), 1);
+    assert_eq!(fast_client.read().unwrap(), Message::text("4"));
+
+    let metrics = hub.metrics();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].delivered, 5);
+    assert!(!metrics[0].disconnected);
+    assert_eq!((metrics[1].delivered, metrics[1].dropped), (0, 5));
+    assert!(metrics[1].disconnected);
+
+    let slow_server = serve(&listener, slow);
+    let (mut slow_client, _) = connect(&url).unwrap();
+    match slow_client.read().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Policy),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    while slow_client.read().is_ok() {}
+    slow_server.join().unwrap();
+
+    hub.close();
+    match fast_client.read().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    while fast_client.read().is_ok() {}
+    fast_server.join().unwrap();
+    assert_eq!(hub.subscriber_count(), 0);
+}
+
+#[test]
+fn non_blocking_writes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let hub = Hub::new(HubConfig { queue_capacity: 1 << 16, ..HubConfig::default() });
+    let subscription = hub.subscribe();
+    subscription.subscribe_to("bulk");
+
+    let client = spawn(move || {
+        let (mut socket, _) = connect(format!("ws://{addr}/bulk")).unwrap();
+        let mut received = 0;
+        while let Ok(Message::Binary(_)) = socket.read() {
+            received += 1;
+        }
+        received
+    });
+    // The write buffer holds a few messages, the rest stays queued in the hub.
+    let config = WebSocketConfig { max_write_buffer_size: 256 << 10, ..WebSocketConfig::default() };
+    let mut socket = accept_with_config(listener.accept().unwrap().0, Some(config)).unwrap();
+    socket.get_mut().set_nonblocking(true).unwrap();
+
+    // Publish more than the socket buffers hold, the writes must not block.
+    for _ in 0..2000 {
+        hub.publish("bulk", Message::binary(vec![0; 16 << 10]));
+    }
+    subscription.write_to(&mut socket).unwrap();
+    assert!(subscription.metrics().queued > 0);
+
+    hub.close();
+    while subscription.metrics().queued > 0 || socket.can_write() {
+        subscription.write_to(&mut socket).unwrap();
+        sleep(Duration::from_millis(1));
+    }
+    socket.get_mut().set_nonblocking(false).unwrap();
+    socket.flush().unwrap();
+    while socket.read().is_ok() {}
+    assert_eq!(client.join().unwrap(), 2000);
+}
@@ -0,0 +1,170 @@
+//! Serving sessions with `listener::Server`.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    io::Read,
+    net::{SocketAddr, TcpStream},
+    sync::mpsc,
+    thread::{spawn, JoinHandle},
+    time::Duration,
+};
+
+use tungstenite::{
+    client,
+    handshake::HandshakeConfig,
+    http::StatusCode,
+    listener::{Server, ServerConfig, ShutdownHandle},
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Error, HandshakeError, Message, WebSocket,
+};
+
+fn start(server: Server) -> (SocketAddr, ShutdownHandle, JoinHandle<()>) {
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+    (addr, shutdown, spawn(move || server.run().unwrap()))
+}
+
+fn echo_server(config: ServerConfig) -> Server {
+    Server::bind("127.0.0.1:0", config).unwrap().with_route("/echo", |socket, _| loop {
+        let msg = socket.read()?;
+        if msg.is_text() || msg.is_binary() {
+            socket.send(msg)?;
+        }
+    })
+}
+
+fn connect(addr: SocketAddr, url: &str) -> Result<WebSocket<TcpStream>, StatusCode> {
+    match client(url, TcpStream::connect(addr).unwrap()) {
+        Ok((socket, _)) => Ok(socket),
+        Err(HandshakeError::Failure(Error::Http(response))) => Err(response.status()),
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+/// The response the server sent without reading a request.
+fn rejection(addr: SocketAddr) -> String {
+    let mut response = String::new();
+    TcpStream::connect(addr).unwrap().read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn routing_by_path_and_host() {
+    let server = Server::bind("127.0.0.1:0", ServerConfig::default())
+        .unwrap()
+        .with_host_route("admin.test", "/status", |socket, _| socket.send(Message::text("admin")))
+        .with_route("/status", |socket, request| {
+            let path = request.uri().path_and_query().unwrap().to_string();
+            socket.send(Message::text(format!("public {path}")))
+        });
+    let (addr, shutdown, server) = start(server);
+
+    let mut socket = connect(addr, "ws://ADMIN.test:8080/status").unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("admin"));
+    let mut socket = connect(addr, "ws://other.test/status?x=1").unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("public /status?x=1"));
+    assert_eq!(connect(addr, "ws://admin.test/missing").err(), Some(StatusCode::NOT_FOUND));
+
+    // The server closes the session when the handler returns.
+    assert!(matches!(
+        socket.read(),
+        Ok(Message::Close(Some(CloseFrame { code: CloseCode::Normal, .. })))
+    ));
+
+    shutdown.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn connection_limits() {
+    let config = ServerConfig { max_connections_per_ip: 1, ..ServerConfig::default() };
+    let (addr, shutdown, server) = start(echo_server(config));
+    let mut socket = connect(addr, "ws://localhost/echo").unwrap();
+    socket.send(Message::text("first")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("first"));
+    assert!(rejection(addr).starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    drop(socket);
+    shutdown.shutdown();
+    server.join().unwrap();
+
+    let config = ServerConfig { max_connections: 1, ..ServerConfig::default() };
+    let (addr, shutdown, server) = start(echo_server(config));
+    let mut socket = connect(addr, "ws://localhost/echo").unwrap();
+    socket.send(Message::text("first")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("first"));
+    assert!(rejection(addr).starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    shutdown.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn idle_handshake_times_out() {
+    let timeout = Some(Duration::from_millis(100));
+    let config = ServerConfig {
+        threads: 1,
+        handshake: Some(HandshakeConfig::default().timeout(timeout)),
+        ..ServerConfig::default()
+    };
+    assert!(ServerConfig::default().handshake.unwrap().timeout.is_some());
+    let (addr, shutdown, server) = start(echo_server(config));
+
+    // A client that never sends its request only blocks the pool until the timeout.
+    let mut idle = TcpStream::connect(addr).unwrap();
+    let mut socket = connect(addr, "ws://localhost/echo").unwrap();
+    socket.send(Message::text("Hello")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("Hello"));
+    assert_eq!(idle.read(&mut [0]).unwrap(), 0);
+
+    drop(socket);
+    shutdown.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn queued_connections() {
+    let config = ServerConfig { threads: 1, ..ServerConfig::default() };
+    let (addr, shutdown, server) = start(echo_server(config));
+    let mut first = connect(addr, "ws://localhost/echo").unwrap();
+    first.send(Message::text("first")).unwrap();
+    assert_eq!(first.read().unwrap(), Message::text("first"));
+
+    // The handshake of the second connection waits for the thread running the first session.
+    let (sender, receiver) = mpsc::channel();
+    let second = spawn(move || {
+        let mut second = connect(addr, "ws://localhost/echo").unwrap();
+        sender.send(()).unwrap();
+        second.send(Message::text("second")).unwrap();
+        assert_eq!(second.read().unwrap(), Message::text("second"));
+    });
+    assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    first.close(None).unwrap();
+    while first.read().is_ok() {}
+    receiver.recv().unwrap();
+    second.join().unwrap();
+
+    shutdown.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn graceful_shutdown() {
+    let (addr, shutdown, server) = start(echo_server(ServerConfig::default()));
+    let mut sockets: Vec<_> =
+        (0..3).map(|_| connect(addr, "ws://localhost/echo").unwrap()).collect();
+    for socket in &mut sockets {
+        socket.send(Message::text("ping")).unwrap();
+        assert_eq!(socket.read().unwrap(), Message::text("ping"));
+    }
+
+    shutdown.shutdown();
+    assert!(shutdown.is_shut_down());
+    for socket in &mut sockets {
+        match socket.read() {
+            Ok(Message::Close(Some(frame))) => assert_eq!(frame.code, CloseCode::Away),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+    server.join().unwrap();
+    assert!(TcpStream::connect(addr).is_err());
+}
@@ -0,0 +1,279 @@
+//! Connecting through local stand-in HTTP CONNECT and SOCKS5 proxies.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    thread::{spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tungstenite::{
+    accept,
+    client::{connect_with_options, ConnectOptions},
+    error::ProxyError,
+    proxy::{Proxy, ProxyConfig},
+    Error, Message,
+};
+
+/// Spawn an echo WebSocket server accepting a single connection.
+fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    spawn(move || {
+        let mut ws = accept(listener.accept().unwrap().0).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+    });
+    addr
+}
+
+/// Spawn a proxy accepting a single CONNECT request, returns the request head it received.
+fn proxy(authorization: Option<&'static str>) -> (SocketAddr, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            client.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+
+        let authorized = authorization.map_or(true, |expected| {
+            head.lines().any(|line| line == format!("Proxy-Authorization: {expected}"))
+        });
+        if !authorized {
+            client.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").unwrap();
+            return head;
+        }
+
+        let target = head.split(' ').nth(1).unwrap();
+        let server = TcpStream::connect(target).unwrap();
+        client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap();
+
+        pipe(client, server);
+        head
+    });
+    (addr, handle)
+}
+
+/// Spawn a SOCKS5 proxy accepting a single connection, returns the address type and address
+/// of the `CONNECT` request it received.
+fn socks5_proxy(
+    credentials: Option<(&'static str, &'static str)>,
+) -> (SocketAddr, JoinHandle<(u8, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        let mut buf = [0; 2];
+        client.read_exact(&mut buf).unwrap();
+        let mut methods = vec![0; buf[1] as usize];
+        client.read_exact(&mut methods).unwrap();
+
+        if let Some((user, password)) = credentials {
+            assert!(methods.contains(&2));
+            client.write_all(&[5, 2]).unwrap();
+            client.read_exact(&mut buf).unwrap();
+            let mut received_user = vec![0; buf[1] as usize];
+            client.read_exact(&mut received_user).unwrap();
+            client.read_exact(&mut buf[..1]).unwrap();
+            let mut received_password = vec![0; buf[0] as usize];
+            client.read_exact(&mut received_password).unwrap();
+            if received_user != user.as_bytes() || received_password != password.as_bytes() {
+                client.write_all(&[1, 1]).unwrap();
+                return (0, Vec::new());
+            }
+            client.write_all(&[1, 0]).unwrap();
+        } else {
+            client.write_all(&[5, 0]).unwrap();
+        }
+
+        let mut request = [0; 4];
+        client.read_exact(&mut request).unwrap();
+        assert_eq!(&request[..3], &[5, 1, 0]);
+        let address = match request[3] {
+            1 => {
+                let mut ip = vec![0; 4];
+                client.read_exact(&mut ip).unwrap();
+                ip
+            }
+            3 => {
+                client.read_exact(&mut buf[..1]).unwrap();
+                let mut name = vec![0; buf[0] as usize];
+                client.read_exact(&mut name).unwrap();
+                name
+            }
+            atyp => panic!("unexpected address type {}", atyp),
+        };
+        client.read_exact(&mut buf).unwrap();
+        let port = u16::from_be_bytes(buf);
+
+        let server = match request[3] {
+            1 => TcpStream::connect((
+                Ipv4Addr::from(<[u8; 4]>::try_from(&address[..]).unwrap()),
+                port,
+            )),
+            _ => TcpStream::connect((std::str::from_utf8(&address).unwrap(), port)),
+        }
+        .unwrap();
+        client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+        pipe(client, server);
+        (request[3], address)
+    });
+    (addr, handle)
+}
+
+/// Forward data between both streams until they are closed.
+fn pipe(client: TcpStream, server: TcpStream) {
+    let (mut client_read, mut server_write) =
+        (client.try_clone().unwrap(), server.try_clone().unwrap());
+    let upstream = spawn(move || {
+        io::copy(&mut client_read, &mut server_write).ok();
+        server_write.shutdown(Shutdown::Write).ok();
+    });
+    let (mut server_read, mut client_write) = (server, client);
+    io::copy(&mut server_read, &mut client_write).ok();
+    client_write.shutdown(Shutdown::Write).ok();
+    upstream.join().unwrap();
+}
+
+/// Send a message, expect it echoed and close the connection.
+fn echo_roundtrip(options: ConnectOptions, url: String) {
+    let (mut ws, _) = connect_with_options(url, options).unwrap();
+    ws.send(Message::Text("Hello through the proxy".into())).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::Text("Hello through the proxy".into()));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+}
+
+#[test]
+fn connect_through_proxy() {
+    let server = echo_server();
+    let (proxy_addr, proxy_handle) = proxy(Some("Basic dXNlcjpzZWNyZXQ="));
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(Proxy::new(&format!("http://user:secret@{proxy_addr}")).unwrap()),
+        ..ConnectOptions::default()
+    };
+    echo_roundtrip(options, format!("ws://{server}/socket"));
+
+    let head = proxy_handle.join().unwrap();
+    assert!(head.starts_with(&format!("CONNECT {server} HTTP/1.1\r\n")));
+}
+
+#[test]
+fn proxy_rejects_connect() {
+    let server = echo_server();
+    let (proxy_addr, proxy_handle) = proxy(Some("Basic dXNlcjpzZWNyZXQ="));
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(Proxy::new(&format!("http://{proxy_addr}")).unwrap()),
+        ..ConnectOptions::default()
+    };
+    let err = connect_with_options(format!("ws://{server}/socket"), options).unwrap_err();
+    assert!(matches!(err, Error::Proxy(ProxyError::ConnectFailed(407))));
+    proxy_handle.join().unwrap();
+
+    // Let the server thread finish.
+    drop(TcpStream::connect(server));
+}
+
+#[test]
+fn oversized_proxy_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy_handle = spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        client.write_all(b"HTTP/1.1 200 Connection established\r\n").unwrap();
+        // Never finish the response head.
+        let header = format!("X-Padding: {}\r\n", "a".repeat(1000));
+        while client.write_all(header.as_bytes()).is_ok() {}
+    });
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(Proxy::new(&format!("http://{proxy_addr}")).unwrap()),
+        ..ConnectOptions::default()
+    };
+    let err = connect_with_options("ws://localhost/socket", options).unwrap_err();
+    assert!(matches!(err, Error::Proxy(ProxyError::InvalidResponse)), "{:?}", err);
+    proxy_handle.join().unwrap();
+}
+
+#[test]
+fn silent_proxy_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy_handle = spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        // Never answer, wait for the client to give up.
+        let mut buf = [0; 1024];
+        while client.read(&mut buf).map_or(false, |n| n > 0) {}
+    });
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(Proxy::new(&format!("socks5://{proxy_addr}")).unwrap()),
+        connect_timeout: Some(Duration::from_millis(100)),
+        ..ConnectOptions::default()
+    };
+    let start = Instant::now();
+    let err = connect_with_options("ws://127.0.0.1/socket", options).unwrap_err();
+    assert!(matches!(err, Error::Io(_)), "{:?}", err);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    proxy_handle.join().unwrap();
+}
+
+#[test]
+fn connect_through_socks5_with_remote_dns() {
+    let server = echo_server();
+    let (proxy_addr, proxy_handle) = socks5_proxy(Some(("user", "secret")));
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(
+            Proxy::new(&format!("socks5h://{proxy_addr}"))
+                .unwrap()
+                .with_credentials("user", "secret"),
+        ),
+        ..ConnectOptions::default()
+    };
+    echo_roundtrip(options, format!("ws://localhost:{}/socket", server.port()));
+
+    assert_eq!(proxy_handle.join().unwrap(), (3, b"localhost".to_vec()));
+}
+
+#[test]
+fn connect_through_socks5_with_local_dns() {
+    let server = echo_server();
+    let (proxy_addr, proxy_handle) = socks5_proxy(None);
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(Proxy::new(&format!("socks5://{proxy_addr}")).unwrap()),
+        ..ConnectOptions::default()
+    };
+    echo_roundtrip(options, format!("ws://{server}/socket"));
+
+    assert_eq!(proxy_handle.join().unwrap(), (1, vec![127, 0, 0, 1]));
+}
+
+#[test]
+fn socks5_authentication_failure() {
+    let (proxy_addr, proxy_handle) = socks5_proxy(Some(("user", "secret")));
+
+    let options = ConnectOptions {
+        proxy: ProxyConfig::Proxy(
+            Proxy::new(&format!("socks5h://user:wrong@{proxy_addr}")).unwrap(),
+        ),
+        ..ConnectOptions::default()
+    };
+    let err = connect_with_options("ws://localhost/socket", options).unwrap_err();
+    assert!(matches!(err, Error::Proxy(ProxyError::SocksAuthenticationFailed)));
+    proxy_handle.join().unwrap();
+}
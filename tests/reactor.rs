@@ -0,0 +1,263 @@
+//! Serving non-blocking connections with `reactor::Reactor`.
+
+#![cfg(all(feature = "mio", feature = "handshake"))]
+
+use std::{
+    net::{SocketAddr, TcpListener as StdListener},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
+};
+
+use tungstenite::{
+    accept, client, connect,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    reactor::{Event, Reactor, ReactorConfig},
+    HandshakeError, Message,
+};
+
+struct Running {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Running {
+    fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.join().unwrap();
+    }
+}
+
+/// Run a reactor listening on localhost in a thread.
+fn start<F>(mut handler: F) -> Running
+where
+    F: FnMut(&mut Reactor, Event) + Send + 'static,
+{
+    let listener = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let thread = spawn(move || {
+        let mut reactor = Reactor::new(ReactorConfig::default()).unwrap();
+        reactor.listen(listener).unwrap();
+        while !stopped.load(Ordering::SeqCst) {
+            reactor.poll(Some(Duration::from_millis(10)), &mut handler).unwrap();
+        }
+    });
+    Running { addr, stop, thread }
+}
+
+/// Echo data messages, reporting the other events as text.
+fn echo(events: mpsc::Sender<String>) -> impl FnMut(&mut Reactor, Event) + Send + 'static {
+    move |reactor, event| match event {
+        Event::Open { request, .. } => events.send(format!("open {}", request.uri())).unwrap(),
+        Event::Message { id, message } if message.is_text() || message.is_binary() => {
+            reactor.send(id, message).unwrap()
+        }
+        Event::Message { .. } => {}
+        Event::Closed { error, .. } => events.send(format!("closed {error:?}")).unwrap(),
+        _ => {}
+    }
+}
+
+#[test]
+fn echo_and_close_handshake() {
+    let (sender, events) = mpsc::channel();
+    let server = start(echo(sender));
+
+    let (mut socket, _) = connect(format!("ws://{}/echo?a=1", server.addr)).unwrap();
+    assert_eq!(events.recv().unwrap(), "open /echo?a=1");
+    socket.send(Message::text("Hello")).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::text("Hello"));
+    socket.send(Message::binary(vec![1, 2, 3])).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::binary(vec![1, 2, 3]));
+
+    socket.close(None).unwrap();
+    assert!(matches!(socket.read(), Ok(Message::Close(None))));
+    assert_eq!(events.recv().unwrap(), "closed None");
+    server.stop();
+}
+
+#[test]
+fn many_clients() {
+    let (sender, events) = mpsc::channel();
+    let server = start(echo(sender));
+    let addr = server.addr;
+
+    let clients: Vec<_> = (0..20)
+        .map(|i| {
+            spawn(move || {
+                let (mut socket, _) = connect(format!("ws://{addr}/{i}")).unwrap();
+                for j in 0..10 {
+                    let text = format!("{i}-{j}");
+                    socket.send(Message::text(text.clone())).unwrap();
+                    assert_eq!(socket.read().unwrap(), Message::text(text));
+                }
+                socket.close(None).unwrap();
+                while socket.read().is_ok() {}
+            })
+        })
+        .collect();
+    for client in clients {
+        client.join().unwrap();
+    }
+    let events: Vec<_> = events.iter().take(40).collect();
+    assert_eq!(events.iter().filter(|e| e.starts_with("open")).count(), 20);
+    assert_eq!(events.iter().filter(|e| *e == "closed None").count(), 20);
+    server.stop();
+}
+
+#[test]
+fn large_message_waits_for_writable() {
+    let (sender, events) = mpsc::channel();
+    let server = start(echo(sender));
+
+    let (mut socket, _) = connect(format!("ws://{}/", server.addr)).unwrap();
+    let payload: Vec<u8> = (0..16 << 20).map(|i| i as u8).collect();
+    socket.send(Message::binary(payload.clone())).unwrap();
+    // Don't read for a while, the reply doesn't fit into the socket buffers.
+    sleep(Duration::from_millis(50));
+    assert_eq!(socket.read().unwrap(), Message::binary(payload));
+
+    drop(socket);
+    assert_eq!(events.recv().unwrap(), "open /");
+    assert!(events.recv().unwrap().starts_with("closed Some("));
+    server.stop();
+}
+
+#[test]
+fn server_initiated_close() {
+    let (sender, events) = mpsc::channel();
+    let server = start(move |reactor, event| match event {
+        Event::Open { id, .. } => {
+            let frame = CloseFrame { code: CloseCode::Away, reason: "bye".into() };
+            reactor.close(id, Some(frame)).unwrap();
+            assert!(reactor.send(id, Message::text("late")).is_err());
+        }
+        Event::Closed { error, .. } => sender.send(error.is_none()).unwrap(),
+        _ => {}
+    });
+
+    let (mut socket, _) = connect(format!("ws://{}/", server.addr)).unwrap();
+    match socket.read().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    while socket.read().is_ok() {}
+    assert!(events.recv().unwrap());
+    server.stop();
+}
+
+#[test]
+fn busy_connection_yields() {
+    let listener = mio::net::TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ReactorConfig { max_messages_per_poll: 4, ..ReactorConfig::default() };
+    let mut reactor = Reactor::new(config).unwrap();
+    reactor.listen(listener).unwrap();
+
+    let (sent, all_sent) = mpsc::channel();
+    let client = spawn(move || {
+        let (mut socket, _) = connect(format!("ws://{addr}/")).unwrap();
+        for i in 0..20 {
+            socket.write(Message::text(i.to_string())).unwrap();
+        }
+        socket.flush().unwrap();
+        sent.send(()).unwrap();
+        while socket.read().is_ok() {}
+    });
+
+    // The first messages may already be read by the poll that opens the connection.
+    let mut id = None;
+    let mut received = Vec::new();
+    while id.is_none() || received.len() < 20 {
+        let mut batch = 0;
+        reactor
+            .poll(Some(Duration::from_secs(5)), |_, event| match event {
+                Event::Open { id: opened, .. } => id = Some(opened),
+                Event::Message { message, .. } => {
+                    received.push(message.into_text().unwrap().to_string());
+                    batch += 1;
+                }
+                _ => {}
+            })
+            .unwrap();
+        assert!(batch <= 4, "{} messages in one poll", batch);
+    }
+    all_sent.recv().unwrap();
+    assert_eq!(received, (0..20).map(|i| i.to_string()).collect::<Vec<_>>());
+
+    reactor.close(id.unwrap(), None).unwrap();
+    let mut closed = false;
+    while !closed {
+        reactor
+            .poll(Some(Duration::from_secs(5)), |_, event| {
+                closed |= matches!(event, Event::Closed { error: None, .. })
+            })
+            .unwrap();
+    }
+    client.join().unwrap();
+}
+
+#[test]
+fn registered_client_connection() {
+    let listener = StdListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let mut socket = accept(listener.accept().unwrap().0).unwrap();
+        socket.send(Message::text("welcome")).unwrap();
+        let message = socket.read().unwrap();
+        socket.send(message).unwrap();
+        while socket.read().is_ok() {}
+    });
+
+    let stream = mio::net::TcpStream::connect(addr).unwrap();
+    let mut handshake = client(format!("ws://{addr}/"), stream);
+    let socket = loop {
+        match handshake {
+            Ok((socket, _)) => break socket,
+            Err(HandshakeError::Interrupted(mid)) => {
+                sleep(Duration::from_millis(1));
+                handshake = mid.handshake();
+            }
+            Err(HandshakeError::Failure(err)) => panic!("handshake failed: {}", err),
+        }
+    };
+
+    let mut reactor = Reactor::new(ReactorConfig::default()).unwrap();
+    let id = reactor.register(socket).unwrap();
+    assert_eq!(reactor.connection_count(), 1);
+    let mut received = Vec::new();
+    let mut closed = false;
+    while !closed {
+        reactor
+            .poll(Some(Duration::from_secs(5)), |reactor, event| match event {
+                Event::Message { id, message } if message == Message::text("welcome") => {
+                    reactor.send(id, Message::text("echo me")).unwrap();
+                    received.push(message);
+                }
+                Event::Message { message, .. } if message.is_close() => received.push(message),
+                Event::Message { id, message } => {
+                    received.push(message);
+                    reactor.close(id, None).unwrap();
+                }
+                Event::Closed { id: closed_id, error } => {
+                    assert_eq!(closed_id, id);
+                    assert!(error.is_none(), "{:?}", error);
+                    closed = true;
+                }
+                other => panic!("unexpected event: {:?}", other),
+            })
+            .unwrap();
+    }
+    assert_eq!(
+        received,
+        [Message::text("welcome"), Message::text("echo me"), Message::Close(None)]
+    );
+    assert_eq!(reactor.connection_count(), 0);
+    server.join().unwrap();
+}
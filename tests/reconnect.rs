@@ -0,0 +1,111 @@
+//! Reconnecting after the server closed the connection.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::spawn,
+    time::Duration,
+};
+
+use tungstenite::{
+    accept,
+    client::ConnectOptions,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    reconnect::{ReconnectConfig, ReconnectingClient},
+    Error, Message,
+};
+
+fn config() -> ReconnectConfig {
+    ReconnectConfig {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+        max_attempts: Some(5),
+        restart_delay: Duration::from_millis(10)..Duration::from_millis(20),
+        ..ReconnectConfig::default()
+    }
+}
+
+#[test]
+fn reconnect_after_restart() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        for connection in 0..2 {
+            let mut ws = accept(listener.accept().unwrap().0).unwrap();
+            assert_eq!(ws.read().unwrap(), Message::text("subscribe"));
+            ws.send(Message::text(format!("update {connection}"))).unwrap();
+            if connection == 0 {
+                let frame = CloseFrame { code: CloseCode::Restart, reason: "restarting".into() };
+                ws.close(Some(frame)).unwrap();
+            }
+            while ws.read().is_ok() {}
+        }
+    });
+
+    let connects = Arc::new(AtomicUsize::new(0));
+    let mut client =
+        ReconnectingClient::new([format!("ws://{addr}/feed")], ConnectOptions::default(), config())
+            .unwrap();
+    let counter = connects.clone();
+    client.set_on_connect(move |socket, _| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        socket.send(Message::text("subscribe"))
+    });
+
+    assert_eq!(client.read().unwrap(), Message::text("update 0"));
+    match client.read().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Restart),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    assert_eq!(client.read().unwrap(), Message::text("update 1"));
+    assert_eq!(connects.load(Ordering::SeqCst), 2);
+
+    client.close(None).unwrap();
+    assert_eq!(client.read().unwrap(), Message::Close(None));
+    assert!(matches!(client.read(), Err(Error::ConnectionClosed)));
+    assert!(matches!(client.read(), Err(Error::AlreadyClosed)));
+    server.join().unwrap();
+}
+
+#[test]
+fn rotate_endpoints() {
+    let unreachable = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let mut ws = accept(listener.accept().unwrap().0).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+    });
+
+    let endpoints = [format!("ws://{unreachable}/"), format!("ws://{addr}/")];
+    let mut client =
+        ReconnectingClient::new(endpoints, ConnectOptions::default(), config()).unwrap();
+    client.send(Message::text("Hello")).unwrap();
+    assert_eq!(client.endpoint(), 1);
+    assert_eq!(client.read().unwrap(), Message::text("Hello"));
+
+    client.close(None).unwrap();
+    assert_eq!(client.read().unwrap(), Message::Close(None));
+    assert!(matches!(client.read(), Err(Error::ConnectionClosed)));
+    assert!(matches!(client.read(), Err(Error::AlreadyClosed)));
+    server.join().unwrap();
+}
+
+#[test]
+fn give_up_after_max_attempts() {
+    let unreachable = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut client = ReconnectingClient::new(
+        [format!("ws://{unreachable}/")],
+        ConnectOptions::default(),
+        config(),
+    )
+    .unwrap();
+    assert!(client.connect().is_err());
+}
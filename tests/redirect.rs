@@ -0,0 +1,118 @@
+//! Following redirects of the handshake request.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener},
+    thread::{spawn, JoinHandle},
+};
+
+use http::{header::AUTHORIZATION, HeaderMap, Uri};
+use tungstenite::{
+    accept_hdr,
+    client::{connect_with_options, ConnectOptions, RedirectChain, RedirectPolicy},
+    handshake::server::{Request, Response},
+    ClientRequestBuilder, Error, Message,
+};
+
+/// Answer the next request on the listener with a redirect to `location`.
+fn redirect(listener: &TcpListener, location: &str) {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    write!(stream, "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+}
+
+/// Accept a WebSocket on the listener, echo one message and return the handshake request.
+fn accept_echo(listener: TcpListener) -> JoinHandle<(Uri, HeaderMap)> {
+    spawn(move || {
+        let mut request = None;
+        let callback = |req: &Request, res: Response| {
+            request = Some((req.uri().clone(), req.headers().clone()));
+            Ok(res)
+        };
+        let mut ws = accept_hdr(listener.accept().unwrap().0, callback).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+        request.unwrap()
+    })
+}
+
+fn echo_roundtrip(request: ClientRequestBuilder, options: ConnectOptions) -> RedirectChain {
+    let (mut ws, response) = connect_with_options(request, options).unwrap();
+    ws.send(Message::Text("Hello".into())).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::Text("Hello".into()));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    response.extensions().get::<RedirectChain>().unwrap().clone()
+}
+
+fn bind() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+#[test]
+fn relative_redirect() {
+    let (listener, addr) = bind();
+    let server = spawn(move || {
+        redirect(&listener, "v2?token=1");
+        accept_echo(listener).join().unwrap()
+    });
+
+    let uri: Uri = format!("ws://{addr}/ws/v1").parse().unwrap();
+    let request = ClientRequestBuilder::new(uri).with_header("Authorization", "Bearer token");
+    let chain = echo_roundtrip(request, ConnectOptions::default());
+
+    let (uri, headers) = server.join().unwrap();
+    assert_eq!(uri, "/ws/v2?token=1");
+    assert_eq!(headers[AUTHORIZATION], "Bearer token");
+    assert_eq!(
+        chain.0,
+        vec![
+            format!("ws://{addr}/ws/v1").parse::<Uri>().unwrap(),
+            format!("ws://{addr}/ws/v2?token=1").parse().unwrap()
+        ]
+    );
+}
+
+#[test]
+fn cross_origin_redirect_strips_credentials() {
+    let (first, first_addr) = bind();
+    let (second, second_addr) = bind();
+    let second = accept_echo(second);
+    let location = format!("ws://localhost:{}/socket", second_addr.port());
+    let first = spawn(move || redirect(&first, &location));
+
+    let uri: Uri = format!("ws://{first_addr}/socket").parse().unwrap();
+    let request = ClientRequestBuilder::new(uri).with_header("Authorization", "Bearer token");
+    let chain = echo_roundtrip(request, ConnectOptions::default());
+    first.join().unwrap();
+
+    let (_, headers) = second.join().unwrap();
+    assert!(!headers.contains_key(AUTHORIZATION));
+    assert_eq!(headers["Host"], format!("localhost:{}", second_addr.port()).as_str());
+    assert_eq!(chain.0.len(), 2);
+}
+
+#[test]
+fn cross_host_redirect_refused() {
+    let (listener, addr) = bind();
+    let server = spawn(move || redirect(&listener, "ws://localhost/socket"));
+
+    let options = ConnectOptions {
+        redirects: RedirectPolicy { allow_cross_host: false, ..RedirectPolicy::default() },
+        ..ConnectOptions::default()
+    };
+    let err = connect_with_options(format!("ws://{addr}/socket"), options).unwrap_err();
+    assert!(matches!(err, Error::Url(tungstenite::error::UrlError::CrossHostRedirect(_))));
+    server.join().unwrap();
+}
@@ -0,0 +1,60 @@
+//! Resolving host names with a custom resolver.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    io,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    thread::spawn,
+};
+
+use tungstenite::{
+    accept_hdr,
+    client::{connect_with_options, ConnectOptions},
+    handshake::server::{Request, Response},
+    Error, Message,
+};
+
+fn options(server: SocketAddr) -> ConnectOptions {
+    ConnectOptions {
+        resolver: Arc::new(move |host: &str, port: u16| match host {
+            "echo.test" if port == 80 => Ok(vec![server]),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }),
+        ..ConnectOptions::default()
+    }
+}
+
+#[test]
+fn connect_with_custom_resolver() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let mut host = None;
+        let callback = |req: &Request, res: Response| {
+            host = req.headers().get("Host").cloned();
+            Ok(res)
+        };
+        let mut ws = accept_hdr(listener.accept().unwrap().0, callback).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+        host.unwrap()
+    });
+
+    let (mut ws, _) = connect_with_options("ws://echo.test/socket", options(addr)).unwrap();
+    ws.send(Message::Text("Hello".into())).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::Text("Hello".into()));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+
+    assert_eq!(server.join().unwrap(), "echo.test");
+}
+
+#[test]
+fn resolver_errors_are_returned() {
+    let addr = "127.0.0.1:1".parse().unwrap();
+    let err = connect_with_options("ws://unknown.test/socket", options(addr)).unwrap_err();
+    assert!(matches!(err, Error::Io(err) if err.kind() == io::ErrorKind::NotFound));
+}
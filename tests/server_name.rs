@@ -0,0 +1,163 @@
+//! Connecting to an address other than the host of the URI, with separate server names.
+
+#![cfg(feature = "handshake")]
+
+use std::{net::TcpListener, thread::spawn};
+
+use tungstenite::{
+    accept_hdr,
+    client::{connect_with_options, ConnectOptions},
+    handshake::server::{Request, Response},
+    Message,
+};
+
+#[test]
+fn connect_addrs_and_host_header() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let mut host = None;
+        let callback = |req: &Request, res: Response| {
+            host = req.headers().get("Host").cloned();
+            Ok(res)
+        };
+        let mut ws = accept_hdr(listener.accept().unwrap().0, callback).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+        host.unwrap()
+    });
+
+    let options = ConnectOptions {
+        // Never resolved, the port of the URI is not used either.
+        connect_addrs: vec!["127.0.0.1:1".parse().unwrap(), addr],
+        host_header: Some("api.example.com".into()),
+        happy_eyeballs_delay: None,
+        ..ConnectOptions::default()
+    };
+    let (mut ws, _) = connect_with_options("ws://service.invalid/socket", options).unwrap();
+    ws.send(Message::text("Hello")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+
+    assert_eq!(server.join().unwrap(), "api.example.com");
+}
+
+#[cfg(feature = "__rustls-tls")]
+mod tls {
+    use std::{
+        net::{SocketAddr, TcpStream},
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+    };
+
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig,
+    };
+    use tungstenite::{
+        accept_tls, client_tls_with_server_name, stream::MaybeTlsStream, Acceptor, Connector,
+        TlsOptions,
+    };
+
+    use super::*;
+
+    /// The server name requested by the client and the negotiated ALPN protocol.
+    type Negotiated = (Option<String>, Option<Vec<u8>>);
+
+    /// An echo server with a self-signed certificate for `service.internal`, returning the
+    /// requested server name and the negotiated ALPN protocol.
+    fn server() -> (SocketAddr, JoinHandle<Negotiated>) {
+        let key_pair = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["service.internal".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.der().clone()], key)
+            .unwrap();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = spawn(move || {
+            let stream = listener.accept().unwrap().0;
+            let mut ws = accept_tls(stream, Acceptor::Rustls(Arc::new(config))).unwrap();
+            let sni = match ws.get_ref() {
+                MaybeTlsStream::RustlsServer(tls) => tls.conn.server_name().map(str::to_owned),
+                _ => panic!("not a rustls stream"),
+            };
+            let negotiated = (sni, ws.get_ref().tls_info().unwrap().alpn_protocol);
+            let msg = ws.read().unwrap();
+            ws.send(msg).unwrap();
+            while ws.read().is_ok() {}
+            negotiated
+        });
+        (addr, server)
+    }
+
+    /// Options accepting the self-signed certificate, recording the verified server names.
+    fn options(names: Arc<Mutex<Vec<String>>>) -> TlsOptions {
+        TlsOptions {
+            verify_roots: false,
+            alpn_protocols: vec!["http/1.1".into()],
+            verify: Some(Arc::new(move |_, _, name| {
+                names.lock().unwrap().push(name.to_owned());
+                Ok(())
+            })),
+            ..TlsOptions::default()
+        }
+    }
+
+    #[test]
+    fn server_name_for_ip_address() {
+        let (addr, server) = server();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let connector = Connector::rustls_with_options(&options(names.clone())).unwrap();
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let url = format!("wss://{addr}/socket");
+        let (mut ws, _) =
+            client_tls_with_server_name(url, stream, None, Some(connector), "service.internal")
+                .unwrap();
+        ws.send(Message::text("Hello")).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+        ws.close(None).unwrap();
+        while ws.read().is_ok() {}
+
+        let (sni, alpn) = server.join().unwrap();
+        assert_eq!(sni.as_deref(), Some("service.internal"));
+        assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+        assert_eq!(*names.lock().unwrap(), ["service.internal"]);
+    }
+
+    // `connect` uses `native-tls` when both are enabled, which has no verification callbacks.
+    #[cfg(not(feature = "native-tls"))]
+    #[test]
+    fn connect_with_server_name() {
+        let (addr, server) = server();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let options = ConnectOptions {
+            connect_addrs: vec![addr],
+            server_name: Some("service.internal".into()),
+            tls: options(names.clone()),
+            ..ConnectOptions::default()
+        };
+
+        let url = format!("wss://{addr}/socket");
+        let (mut ws, _) = connect_with_options(url, options).unwrap();
+        ws.send(Message::text("Hello")).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+        ws.close(None).unwrap();
+        while ws.read().is_ok() {}
+
+        let (sni, alpn) = server.join().unwrap();
+        assert_eq!(sni.as_deref(), Some("service.internal"));
+        assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+        assert_eq!(*names.lock().unwrap(), ["service.internal"]);
+    }
+}
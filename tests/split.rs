@@ -0,0 +1,128 @@
+//! Reading and writing concurrently with the halves of a split `WebSocket`.
+
+#![cfg(feature = "handshake")]
+
+use std::{
+    net::{TcpListener, TcpStream},
+    thread::{spawn, JoinHandle},
+};
+
+use tungstenite::{
+    accept, client,
+    error::ProtocolError,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Error, Message, WebSocket,
+};
+
+/// Connect a client to a server running `server` in a thread.
+fn pair<F>(server: F) -> (WebSocket<TcpStream>, JoinHandle<()>)
+where
+    F: FnOnce(WebSocket<TcpStream>) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || server(accept(listener.accept().unwrap().0).unwrap()));
+    let (socket, _) = client(format!("ws://{addr}/"), TcpStream::connect(addr).unwrap()).unwrap();
+    (socket, server)
+}
+
+#[test]
+fn concurrent_writers_and_reader() {
+    let (socket, server) = pair(|mut socket| loop {
+        match socket.read() {
+            Ok(msg) if msg.is_text() => socket.send(msg).unwrap(),
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    });
+    let (mut reader, mut writer) = socket.split().unwrap();
+
+    // The reader blocks before anything is sent.
+    let received = spawn(move || {
+        let mut received = Vec::new();
+        while received.len() < 200 {
+            received.push(reader.read().unwrap().into_text().unwrap());
+        }
+        (reader, received)
+    });
+    let writers: Vec<_> = (0..2)
+        .map(|i| {
+            let mut writer = writer.clone();
+            spawn(move || {
+                for j in 0..100 {
+                    writer.send(Message::text(format!("{i}-{j}"))).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in writers {
+        thread.join().unwrap();
+    }
+    let (mut reader, mut received) = received.join().unwrap();
+    received.sort();
+    let mut expected: Vec<_> =
+        (0..2).flat_map(|i| (0..100).map(move |j| format!("{i}-{j}"))).collect();
+    expected.sort();
+    assert_eq!(received, expected);
+
+    writer.close(None).unwrap();
+    assert!(!writer.can_write());
+    assert_eq!(reader.read().unwrap(), Message::Close(None));
+    assert!(matches!(reader.read(), Err(Error::ConnectionClosed)));
+    assert!(matches!(reader.read(), Err(Error::AlreadyClosed)));
+    server.join().unwrap();
+}
+
+#[test]
+fn replies_go_through_the_writer() {
+    let (socket, server) = pair(|mut socket| {
+        socket.send(Message::Ping(vec![1, 2])).unwrap();
+        assert_eq!(socket.read().unwrap(), Message::Pong(vec![1, 2]));
+        let frame = CloseFrame { code: CloseCode::Away, reason: "bye".into() };
+        socket.close(Some(frame)).unwrap();
+        // The close reply of the client.
+        assert!(matches!(socket.read(), Ok(Message::Close(Some(_)))));
+        assert!(matches!(socket.read(), Err(Error::ConnectionClosed)));
+    });
+    let (mut reader, mut writer) = socket.split().unwrap();
+
+    assert_eq!(reader.read().unwrap(), Message::Ping(vec![1, 2]));
+    match reader.read().unwrap() {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+        other => panic!("unexpected message: {:?}", other),
+    }
+    assert!(!reader.can_read());
+    assert!(matches!(
+        writer.send(Message::text("late")),
+        Err(Error::Protocol(ProtocolError::SendAfterClosing))
+    ));
+    // The client waits for the server to close the connection.
+    assert!(matches!(reader.read(), Err(Error::ConnectionClosed)));
+    server.join().unwrap();
+}
+
+#[test]
+fn split_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = spawn(move || {
+        let (mut socket, _) =
+            client(format!("ws://{addr}/"), TcpStream::connect(addr).unwrap()).unwrap();
+        socket.send(Message::text("hello")).unwrap();
+        assert_eq!(socket.read().unwrap(), Message::text("hello"));
+        socket.close(None).unwrap();
+        assert_eq!(socket.read().unwrap(), Message::Close(None));
+        assert!(matches!(socket.read(), Err(Error::ConnectionClosed)));
+    });
+
+    let socket = accept(listener.accept().unwrap().0).unwrap();
+    let (mut reader, mut writer) = socket.split().unwrap();
+    let message = reader.read().unwrap();
+    writer.send(message).unwrap();
+    assert_eq!(reader.read().unwrap(), Message::Close(None));
+    // The server replied and terminated the connection.
+    assert!(matches!(reader.read(), Err(Error::ConnectionClosed)));
+    assert!(matches!(writer.send(Message::text("late")), Err(Error::AlreadyClosed)));
+    drop((reader, writer));
+    client.join().unwrap();
+}
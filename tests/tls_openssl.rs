@@ -0,0 +1,175 @@
+//! Connecting and accepting `wss://` connections with `openssl`.
+
+#![cfg(all(feature = "handshake", feature = "openssl"))]
+
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
+};
+
+use openssl::{
+    pkey::PKey,
+    ssl::{SslAcceptor, SslConnector, SslMethod},
+    x509::X509,
+};
+use rcgen::{CertificateParams, KeyPair};
+use tungstenite::{
+    accept_tls, client_tls_with_config, error::TlsError, stream::MaybeTlsStream, Acceptor,
+    Connector, Error, HandshakeError, Message, TlsOptions,
+};
+
+/// An echo server with a self-signed certificate for `localhost`, offering ALPN `http/1.1`.
+fn server(delay: Duration) -> (SocketAddr, X509, JoinHandle<()>) {
+    let key = KeyPair::generate().unwrap();
+    let cert =
+        CertificateParams::new(vec!["localhost".to_owned()]).unwrap().self_signed(&key).unwrap();
+    let cert = X509::from_der(cert.der()).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&PKey::private_key_from_der(&key.serialize_der()).unwrap()).unwrap();
+    acceptor.set_alpn_select_callback(|_, client| {
+        openssl::ssl::select_next_proto(b"\x08http/1.1", client)
+            .ok_or(openssl::ssl::AlpnError::NOACK)
+    });
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        sleep(delay);
+        if let Ok(mut ws) = accept_tls(stream, Acceptor::OpenSsl(acceptor)) {
+            assert!(ws.get_ref().tls_info().unwrap().peer_certificates.is_empty());
+            if let Ok(msg) = ws.read() {
+                ws.send(msg).unwrap();
+            }
+            while ws.read().is_ok() {}
+        }
+    });
+    (addr, cert, server)
+}
+
+fn connector(cert: &X509) -> Connector {
+    let mut connector = SslConnector::builder(SslMethod::tls_client()).unwrap();
+    connector.cert_store_mut().add_cert(cert.clone()).unwrap();
+    connector.set_alpn_protos(b"\x08http/1.1").unwrap();
+    Connector::OpenSsl(connector.build())
+}
+
+#[test]
+fn openssl_connection() {
+    let (addr, cert, server) = server(Duration::ZERO);
+    let stream = TcpStream::connect(addr).unwrap();
+    let (mut ws, _) =
+        client_tls_with_config("wss://localhost/socket", stream, None, Some(connector(&cert)))
+            .unwrap();
+    ws.send(Message::text("Hello over OpenSSL")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello over OpenSSL"));
+
+    assert!(matches!(ws.get_ref(), MaybeTlsStream::OpenSsl(_)));
+    let info = ws.get_ref().tls_info().unwrap();
+    assert_eq!(info.version.as_deref(), Some("TLSv1_3"));
+    assert!(info.cipher_suite.is_some());
+    assert_eq!(info.alpn_protocol.as_deref(), Some(&b"http/1.1"[..]));
+    assert_eq!(info.peer_certificates, [cert.to_der().unwrap()]);
+
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    server.join().unwrap();
+}
+
+#[test]
+fn non_blocking_openssl_handshake() {
+    // Make sure the client has to wait for the server hello.
+    let (addr, cert, server) = server(Duration::from_millis(50));
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nonblocking(true).unwrap();
+    let mut result =
+        client_tls_with_config("wss://localhost/socket", stream, None, Some(connector(&cert)));
+    let mut interruptions = 0;
+    let (mut ws, _) = loop {
+        match result {
+            Ok(connected) => break connected,
+            Err(HandshakeError::Interrupted(mid)) => {
+                interruptions += 1;
+                sleep(Duration::from_millis(1));
+                result = mid.handshake();
+            }
+            Err(HandshakeError::Failure(err)) => panic!("handshake failed: {}", err),
+        }
+    };
+    assert!(interruptions > 0);
+    assert!(matches!(ws.get_ref(), MaybeTlsStream::OpenSsl(_)));
+
+    ws.send(Message::text("Hello")).unwrap();
+    let reply = loop {
+        match ws.read() {
+            Ok(msg) => break msg,
+            Err(Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                sleep(Duration::from_millis(1))
+            }
+            Err(err) => panic!("read failed: {}", err),
+        }
+    };
+    assert_eq!(reply, Message::text("Hello"));
+
+    drop(ws);
+    server.join().unwrap();
+}
+
+#[test]
+fn untrusted_certificate() {
+    let (addr, _, server) = server(Duration::ZERO);
+    let stream = TcpStream::connect(addr).unwrap();
+    let connector = Connector::openssl_with_options(&Default::default()).unwrap();
+    let err = client_tls_with_config("wss://localhost/socket", stream, None, Some(connector))
+        .unwrap_err();
+    assert!(
+        matches!(err, HandshakeError::Failure(Error::Tls(TlsError::OpenSsl(_)))),
+        "unexpected error: {:?}",
+        err
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn trusted_root_certificate() {
+    let (addr, cert, server) = server(Duration::ZERO);
+    let stream = TcpStream::connect(addr).unwrap();
+    let options =
+        TlsOptions { root_certificates: vec![cert.to_der().unwrap()], ..TlsOptions::default() };
+    let connector = Connector::openssl_with_options(&options).unwrap();
+    let (mut ws, _) =
+        client_tls_with_config("wss://localhost/socket", stream, None, Some(connector)).unwrap();
+    ws.send(Message::text("Hello")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello"));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    server.join().unwrap();
+}
+
+#[test]
+fn unverified_options_rejected() {
+    let options = TlsOptions { verify_roots: false, ..TlsOptions::default() };
+    match Connector::<TcpStream>::openssl_with_options(&options) {
+        Err(Error::Tls(TlsError::NoVerification)) => {}
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(_) => panic!("connector created without verification"),
+    }
+}
+
+#[test]
+fn invalid_alpn_protocols() {
+    for protocol in [String::new(), "a".repeat(256)] {
+        let options = TlsOptions { alpn_protocols: vec![protocol], ..TlsOptions::default() };
+        match Connector::<TcpStream>::openssl_with_options(&options) {
+            Err(Error::Tls(TlsError::InvalidAlpnProtocol(_))) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("invalid ALPN protocol accepted"),
+        }
+    }
+    let options = TlsOptions { alpn_protocols: vec!["a".repeat(255)], ..TlsOptions::default() };
+    assert!(Connector::<TcpStream>::openssl_with_options(&options).is_ok());
+}
@@ -0,0 +1,147 @@
+//! Verifying self-signed server certificates with public key pins and callbacks.
+
+#![cfg(all(feature = "handshake", feature = "__rustls-tls"))]
+
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread::spawn,
+};
+
+use rcgen::{CertificateParams, CertifiedKey, KeyPair};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+};
+use tungstenite::{
+    accept_tls, client_tls_with_config, error::TlsError, Acceptor, Connector, Error,
+    HandshakeError, Message, TlsOptions,
+};
+
+fn self_signed() -> CertifiedKey {
+    let key_pair = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec!["device.local".to_owned()])
+        .unwrap()
+        .self_signed(&key_pair)
+        .unwrap();
+    CertifiedKey { cert, key_pair }
+}
+
+/// Connect to an echo server presenting `server` with the given client options.
+fn connect(server: &CertifiedKey, options: TlsOptions) -> Result<(), Error> {
+    connect_with_chain(server, Vec::new(), options)
+}
+
+/// Connect to an echo server presenting `server` followed by `intermediates`.
+fn connect_with_chain(
+    server: &CertifiedKey,
+    intermediates: Vec<CertificateDer<'static>>,
+    options: TlsOptions,
+) -> Result<(), Error> {
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server.key_pair.serialize_der()));
+    let mut chain = vec![server.cert.der().clone()];
+    chain.extend(intermediates);
+    let config =
+        ServerConfig::builder().with_no_client_auth().with_single_cert(chain, key).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        if let Ok(mut ws) = accept_tls(stream, Acceptor::Rustls(Arc::new(config))) {
+            let msg = ws.read().unwrap();
+            ws.send(msg).unwrap();
+            while ws.read().is_ok() {}
+        }
+    });
+
+    let connector = Connector::rustls_with_options(&options).unwrap();
+    let stream = TcpStream::connect(addr).unwrap();
+    let result = client_tls_with_config("wss://device.local/", stream, None, Some(connector));
+    let result = match result {
+        Ok((mut ws, _)) => {
+            ws.send(Message::text("Hello device")).unwrap();
+            assert_eq!(ws.read().unwrap(), Message::text("Hello device"));
+            ws.close(None).unwrap();
+            while ws.read().is_ok() {}
+            Ok(())
+        }
+        Err(HandshakeError::Failure(err)) => Err(err),
+        Err(HandshakeError::Interrupted(_)) => panic!("blocking handshake interrupted"),
+    };
+    server.join().unwrap();
+    result
+}
+
+#[test]
+fn pinned_public_key() {
+    let server = self_signed();
+    let pin = TlsOptions::spki_sha256(server.cert.der()).unwrap();
+    let options = TlsOptions { spki_pins: vec![pin], verify_roots: false, ..TlsOptions::default() };
+    connect(&server, options).unwrap();
+}
+
+#[test]
+fn pin_mismatch() {
+    let server = self_signed();
+    let other = TlsOptions::spki_sha256(self_signed().cert.der()).unwrap();
+    let options =
+        TlsOptions { spki_pins: vec![other], verify_roots: false, ..TlsOptions::default() };
+    let err = connect(&server, options).unwrap_err();
+    assert!(matches!(err, Error::Tls(TlsError::PinMismatch)), "unexpected error: {:?}", err);
+}
+
+#[test]
+fn verification_callback() {
+    let server = self_signed();
+    let expected = server.cert.der().clone();
+    let options = TlsOptions {
+        verify_roots: false,
+        verify: Some(Arc::new(move |cert, _, name| {
+            assert_eq!(name, "device.local");
+            if *cert == expected {
+                Ok(())
+            } else {
+                Err("unknown device".to_owned())
+            }
+        })),
+        ..TlsOptions::default()
+    };
+    connect(&server, options.clone()).unwrap();
+
+    let err = connect(&self_signed(), options).unwrap_err();
+    match err {
+        Error::Tls(TlsError::CertificateRejected(reason)) => assert_eq!(reason, "unknown device"),
+        err => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn roots_verified_by_default() {
+    let server = self_signed();
+    let pin = TlsOptions::spki_sha256(server.cert.der()).unwrap();
+    let options = TlsOptions { spki_pins: vec![pin], ..TlsOptions::default() };
+    let err = connect(&server, options).unwrap_err();
+    assert!(matches!(err, Error::Tls(TlsError::Rustls(_))), "unexpected error: {:?}", err);
+}
+
+#[test]
+fn appended_intermediate_does_not_match_pin() {
+    // Without verifying the chain, any server can append a public certificate that is pinned.
+    let pinned = self_signed();
+    let pin = TlsOptions::spki_sha256(pinned.cert.der()).unwrap();
+    let options = TlsOptions { spki_pins: vec![pin], verify_roots: false, ..TlsOptions::default() };
+    let err =
+        connect_with_chain(&self_signed(), vec![pinned.cert.der().clone()], options).unwrap_err();
+    assert!(matches!(err, Error::Tls(TlsError::PinMismatch)), "unexpected error: {:?}", err);
+}
+
+#[test]
+fn unverified_options_rejected() {
+    let options = TlsOptions { verify_roots: false, ..TlsOptions::default() };
+    match Connector::<TcpStream>::rustls_with_options(&options) {
+        Err(Error::Tls(TlsError::NoVerification)) => {}
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(_) => panic!("connector created without verification"),
+    }
+}
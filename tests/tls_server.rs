@@ -0,0 +1,147 @@
+//! Accepting `wss://` connections with `rustls`.
+
+#![cfg(all(feature = "handshake", feature = "__rustls-tls"))]
+
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread::spawn,
+};
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+use rustls::{
+    crypto::ring::{default_provider, sign::any_supported_type},
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    sign::CertifiedKey,
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use tungstenite::{
+    accept_tls, client_tls_with_config, stream::MaybeTlsStream, Acceptor, Connector, Message,
+    SniCertificates, SniResolver,
+};
+
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        Ca { cert: params.self_signed(&key).unwrap(), key }
+    }
+
+    fn issue(&self, name: &str) -> Arc<CertifiedKey> {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec![name.to_owned()])
+            .unwrap()
+            .signed_by(&key, &self.cert, &self.key)
+            .unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        Arc::new(CertifiedKey::new(vec![cert.der().clone()], any_supported_type(&key).unwrap()))
+    }
+
+    fn client_config(&self) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.cert.der().clone()).unwrap();
+        Arc::new(
+            ClientConfig::builder_with_provider(Arc::new(default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    }
+}
+
+fn server_config(resolver: Arc<SniResolver>) -> Arc<ServerConfig> {
+    Arc::new(
+        ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_cert_resolver(resolver),
+    )
+}
+
+/// Connect to `name` served by an echo server and return the certificate the server presented.
+fn connect(
+    config: Arc<ServerConfig>,
+    client: Arc<ClientConfig>,
+    name: &str,
+) -> CertificateDer<'static> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let stream = listener.accept().unwrap().0;
+        let mut ws = accept_tls(stream, Acceptor::Rustls(config)).unwrap();
+        let info = ws.get_ref().tls_info().unwrap();
+        assert!(info.peer_certificates.is_empty());
+        assert_eq!(info.alpn_protocol, None);
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let (mut ws, _) = client_tls_with_config(
+        format!("wss://{name}/socket"),
+        stream,
+        None,
+        Some(Connector::Rustls(client)),
+    )
+    .unwrap();
+    ws.send(Message::text("Hello over TLS")).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::text("Hello over TLS"));
+
+    assert!(matches!(ws.get_ref(), MaybeTlsStream::Rustls(_)));
+    let info = ws.get_ref().tls_info().unwrap();
+    assert_eq!(info.version.as_deref(), Some("TLSv1_3"));
+    assert!(info.cipher_suite.unwrap().starts_with("TLS13_"));
+    let certificate = CertificateDer::from(info.peer_certificates[0].clone());
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+    server.join().unwrap();
+    certificate
+}
+
+#[test]
+fn sni_certificate_selection_and_reload() {
+    let ca = Ca::new();
+    let first = ca.issue("first.test");
+    let second = ca.issue("*.second.test");
+    let resolver = Arc::new(SniResolver::new(
+        SniCertificates::new()
+            .with_certificate("first.test", first.clone())
+            .with_certificate("*.second.test", second.clone()),
+    ));
+    let config = server_config(resolver.clone());
+
+    assert_eq!(connect(config.clone(), ca.client_config(), "first.test"), first.cert[0]);
+    assert_eq!(connect(config.clone(), ca.client_config(), "ws.second.test"), second.cert[0]);
+
+    let renewed = ca.issue("first.test");
+    resolver
+        .set_certificates(SniCertificates::new().with_certificate("first.test", renewed.clone()));
+    assert_eq!(connect(config, ca.client_config(), "first.test"), renewed.cert[0]);
+}
+
+#[test]
+fn reload_with_loader() {
+    let ca = Arc::new(Ca::new());
+    let issuer = ca.clone();
+    let resolver = Arc::new(
+        SniResolver::with_loader(move || {
+            Ok(SniCertificates::new().with_default(issuer.issue("reloaded.test")))
+        })
+        .unwrap(),
+    );
+    let config = server_config(resolver.clone());
+
+    let before = connect(config.clone(), ca.client_config(), "reloaded.test");
+    resolver.reload().unwrap();
+    let after = connect(config, ca.client_config(), "reloaded.test");
+    assert_ne!(before, after);
+}
@@ -0,0 +1,42 @@
+//! Connecting over a Unix domain socket.
+
+#![cfg(all(unix, feature = "handshake"))]
+
+use std::{fs, os::unix::net::UnixListener, process, thread::spawn};
+
+use tungstenite::{
+    accept_hdr, connect,
+    handshake::server::{Request, Response},
+    stream::MaybeTlsStream,
+    Message,
+};
+
+#[test]
+fn connect_over_unix_socket() {
+    let path = std::env::temp_dir().join(format!("tungstenite-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let server = spawn(move || {
+        let mut uri = None;
+        let callback = |req: &Request, res: Response| {
+            uri = Some(req.uri().clone());
+            Ok(res)
+        };
+        let mut ws = accept_hdr(listener.accept().unwrap().0, callback).unwrap();
+        let msg = ws.read().unwrap();
+        ws.send(msg).unwrap();
+        while ws.read().is_ok() {}
+        uri.unwrap()
+    });
+
+    let (mut ws, _) = connect(format!("ws+unix://{}:/chat?room=1", path.display())).unwrap();
+    assert!(matches!(ws.get_ref(), MaybeTlsStream::Unix(_)));
+    ws.send(Message::Text("Hello over Unix".into())).unwrap();
+    assert_eq!(ws.read().unwrap(), Message::Text("Hello over Unix".into()));
+    ws.close(None).unwrap();
+    while ws.read().is_ok() {}
+
+    assert_eq!(server.join().unwrap(), "/chat?room=1");
+    fs::remove_file(&path).unwrap();
+}
@@ -1,4 +1,7 @@
-#![cfg(all(feature = "handshake", not(any(feature = "native-tls", feature = "__rustls-tls"))))]
+#![cfg(all(
+    feature = "handshake",
+    not(any(feature = "native-tls", feature = "openssl", feature = "__rustls-tls"))
+))]
 
 use tungstenite::{connect, error::UrlError, Error};
 